@@ -0,0 +1,274 @@
+// Arbitrary-precision integers, stored as a sign and base 10^9 limbs (least
+// significant first), which makes printing and parsing trivial.  Nothing here
+// is clever; schoolbook multiplication and long division are plenty for the
+// factorials and binomials Curry programs want
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error;
+
+const BASE: u64 = 1_000_000_000;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct BigInt {
+  // Zero is never negative
+  negative: bool,
+  // No leading (i.e., trailing) zero limbs; zero is empty
+  limbs: Vec<u32>
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+  while limbs.last() == Some(&0) {
+    limbs.pop();
+  }
+}
+
+fn compare(a: &[u32], b: &[u32]) -> Ordering {
+  if a.len() != b.len() {
+    return a.len().cmp(&b.len());
+  }
+  for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+    if x != y {
+      return x.cmp(y);
+    }
+  }
+  Ordering::Equal
+}
+
+fn add(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let mut rc = Vec::with_capacity(a.len().max(b.len()) + 1);
+  let mut carry = 0;
+  for i in 0..a.len().max(b.len()) {
+    let sum = u64::from(*a.get(i).unwrap_or(&0)) + u64::from(*b.get(i).unwrap_or(&0)) + carry;
+    rc.push((sum % BASE) as u32);
+    carry = sum / BASE;
+  }
+  if carry > 0 {
+    rc.push(carry as u32);
+  }
+  rc
+}
+
+// a - b, where a >= b
+fn sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let mut rc = Vec::with_capacity(a.len());
+  let mut borrow = 0;
+  for (i, &x) in a.iter().enumerate() {
+    let y = u64::from(*b.get(i).unwrap_or(&0)) + borrow;
+    let x = u64::from(x);
+    if x >= y {
+      rc.push((x - y) as u32);
+      borrow = 0;
+    } else {
+      rc.push((x + BASE - y) as u32);
+      borrow = 1;
+    }
+  }
+  trim(&mut rc);
+  rc
+}
+
+fn mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+  if a.is_empty() || b.is_empty() {
+    return Vec::new();
+  }
+  let mut rc = vec![0u32; a.len() + b.len()];
+  for (i, &x) in a.iter().enumerate() {
+    let mut carry = 0;
+    for (j, &y) in b.iter().enumerate() {
+      let t = u64::from(rc[i + j]) + u64::from(x) * u64::from(y) + carry;
+      rc[i + j] = (t % BASE) as u32;
+      carry = t / BASE;
+    }
+    rc[i + b.len()] = carry as u32;
+  }
+  trim(&mut rc);
+  rc
+}
+
+// Quotient and remainder of a / b, where b isn't zero
+fn div_rem(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+  let mut quotient = Vec::with_capacity(a.len());
+  let mut rem: Vec<u32> = Vec::new();
+  for &limb in a.iter().rev() {
+    rem.insert(0, limb);
+    trim(&mut rem);
+    // Largest digit d with b * d <= rem
+    let (mut low, mut high) = (0, BASE - 1);
+    while low < high {
+      let mid = (low + high).div_ceil(2);
+      if compare(&mul(b, &[mid as u32]), &rem) == Ordering::Greater {
+        high = mid - 1;
+      } else {
+        low = mid;
+      }
+    }
+    if low > 0 {
+      rem = sub(&rem, &mul(b, &[low as u32]));
+    }
+    quotient.push(low as u32);
+  }
+  quotient.reverse();
+  trim(&mut quotient);
+  (quotient, rem)
+}
+
+impl BigInt {
+  fn new(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+    trim(&mut limbs);
+    BigInt { negative: negative && !limbs.is_empty(), limbs }
+  }
+
+  pub fn from_i64(n: i64) -> BigInt {
+    let mut magnitude = n.unsigned_abs();
+    let mut limbs = Vec::new();
+    while magnitude > 0 {
+      limbs.push((magnitude % BASE) as u32);
+      magnitude /= BASE;
+    }
+    BigInt::new(n < 0, limbs)
+  }
+
+  // An optional sign followed by decimal digits, and nothing else
+  pub fn parse(s: &str) -> Option<BigInt> {
+    let (negative, digits) = match s.as_bytes().first() {
+      Some(&b'-') => (true, &s[1..]),
+      Some(&b'+') => (false, &s[1..]),
+      _ => (false, s),
+    };
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+      return None;
+    }
+    let mut limbs = Vec::with_capacity(digits.len() / 9 + 1);
+    let mut end = digits.len();
+    while end > 0 {
+      let start = end.saturating_sub(9);
+      limbs.push(digits[start..end].parse::<u32>().ok()?);
+      end = start;
+    }
+    Some(BigInt::new(negative, limbs))
+  }
+
+  // The exact value of a finite float, rounded towards zero
+  pub fn from_f64(x: f64) -> Option<BigInt> {
+    if x.is_finite() {
+      BigInt::parse(&format!("{:.0}", x.trunc()))
+    } else {
+      None
+    }
+  }
+
+  pub fn to_i64(&self) -> Option<i64> {
+    if self.limbs.len() > 3 {
+      return None;
+    }
+    let mut magnitude: i128 = 0;
+    for &limb in self.limbs.iter().rev() {
+      magnitude = magnitude * BASE as i128 + i128::from(limb);
+    }
+    let n = if self.negative { -magnitude } else { magnitude };
+    if n >= i128::from(i64::MIN) && n <= i128::from(i64::MAX) {
+      Some(n as i64)
+    } else {
+      None
+    }
+  }
+
+  // Nearest float (infinite if it's too big)
+  pub fn to_f64(&self) -> f64 {
+    self.to_string().parse::<f64>().unwrap_or(0.0)
+  }
+
+  // Bits needed for the magnitude, give or take (never fewer than it
+  // really takes, since a limb holds less than 30 bits)
+  pub fn bits(&self) -> u64 {
+    match self.limbs.last() {
+      Some(&top) => (self.limbs.len() as u64 - 1) * 30 + u64::from(32 - top.leading_zeros()),
+      None => 0,
+    }
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.limbs.is_empty()
+  }
+
+  pub fn add(&self, other: &BigInt) -> BigInt {
+    if self.negative == other.negative {
+      return BigInt::new(self.negative, add(&self.limbs, &other.limbs));
+    }
+    match compare(&self.limbs, &other.limbs) {
+      Ordering::Less => BigInt::new(other.negative, sub(&other.limbs, &self.limbs)),
+      _ => BigInt::new(self.negative, sub(&self.limbs, &other.limbs)),
+    }
+  }
+
+  pub fn sub(&self, other: &BigInt) -> BigInt {
+    self.add(&BigInt::new(!other.negative, other.limbs.clone()))
+  }
+
+  pub fn mul(&self, other: &BigInt) -> BigInt {
+    BigInt::new(self.negative != other.negative, mul(&self.limbs, &other.limbs))
+  }
+
+  pub fn pow(&self, mut exponent: u64) -> BigInt {
+    let mut rc = BigInt::from_i64(1);
+    let mut square = self.clone();
+    while exponent > 0 {
+      if exponent % 2 == 1 {
+        rc = rc.mul(&square);
+      }
+      exponent /= 2;
+      if exponent > 0 {
+        square = square.mul(&square);
+      }
+    }
+    rc
+  }
+
+  pub fn abs(&self) -> BigInt {
+    BigInt::new(false, self.limbs.clone())
+  }
+
+  // Truncating division, like integer / and %: the quotient rounds towards
+  // zero and the remainder has the sign of the dividend.  None if other is
+  // zero
+  pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+    if other.is_zero() {
+      return None;
+    }
+    let (q, r) = div_rem(&self.limbs, &other.limbs);
+    Some((BigInt::new(self.negative != other.negative, q), BigInt::new(self.negative, r)))
+  }
+}
+
+impl Ord for BigInt {
+  fn cmp(&self, other: &BigInt) -> Ordering {
+    match (self.negative, other.negative) {
+      (false, true) => Ordering::Greater,
+      (true, false) => Ordering::Less,
+      (false, false) => compare(&self.limbs, &other.limbs),
+      (true, true) => compare(&other.limbs, &self.limbs),
+    }
+  }
+}
+
+impl PartialOrd for BigInt {
+  fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Display for BigInt {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    let mut s = if self.negative { "-".to_string() } else { "".to_string() };
+    match self.limbs.last() {
+      Some(top) => s += &top.to_string(),
+      None => s += "0",
+    }
+    for limb in self.limbs.iter().rev().skip(1) {
+      s += &format!("{:09}", limb);
+    }
+    write!(f, "{}", s)
+  }
+}
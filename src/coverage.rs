@@ -0,0 +1,302 @@
+// Code coverage: counts how many times each call in the source was
+// evaluated, and how many times each function was called.  Calls (and
+// definitions) are the only expressions that know where they are, which is
+// fine, since literals can't run (or not run) on their own.  Lines get the
+// count of the busiest call on them
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+
+use encoding::Block;
+use encoding::Expression;
+
+pub struct Options {
+  // Print annotated source with hit counts
+  pub report: bool,
+  // Write LCOV data to this file
+  pub lcov: Option<String>
+}
+
+struct Function {
+  name: String,
+  lnum: isize,
+  col: usize
+}
+
+struct FileInfo {
+  name: String,
+  lines: Vec<String>,
+  points: Vec<(isize, usize)>,
+  functions: Vec<Function>
+}
+
+struct Coverage {
+  options: Options,
+  files: Vec<FileInfo>,
+  // Index of the file being run
+  current: usize,
+  hits: HashMap<(usize, isize, usize), u64>,
+  function_hits: HashMap<(usize, isize, usize), u64>
+}
+
+thread_local! {
+  static ACTIVE: Cell<bool> = const { Cell::new(false) };
+
+  static COVERAGE: RefCell<Option<Coverage>> = const { RefCell::new(None) };
+}
+
+pub fn start(options: Options) {
+  COVERAGE.with(|c| *c.borrow_mut() = Some(Coverage {
+    options, files: Vec::new(), current: 0, hits: HashMap::new(),
+    function_hits: HashMap::new()
+  }));
+  ACTIVE.with(|a| a.set(true));
+}
+
+pub fn active() -> bool {
+  ACTIVE.with(|a| a.get())
+}
+
+fn with<F: FnOnce(&mut Coverage)>(f: F) {
+  COVERAGE.with(|c| {
+    if let Some(ref mut c) = *c.borrow_mut() {
+      f(c);
+    }
+  });
+}
+
+fn collect(exp: &Expression, info: &mut FileInfo) {
+  match *exp {
+    Expression::Call(ref call) => {
+      info.points.push((call.lnum, call.col));
+      for i in &call.param.items {
+        collect(i, info);
+      }
+    },
+    // Defining a function doesn't say anything about whether its body ran,
+    // so a definition only counts as a function, not as a point on its line
+    Expression::Definition(ref def) => {
+      info.functions.push(Function { name: def.id.clone(), lnum: def.lnum, col: def.col });
+      for e in &def.block.expressions {
+        collect(e, info);
+      }
+    },
+    Expression::List(ref list) => {
+      for i in &list.items {
+        collect(i, info);
+      }
+    },
+    _ => {},
+  }
+}
+
+// Everything from here until the next call to this counts towards this file
+pub fn add_file(name: &str, source: &str, block: &Block) {
+  let mut info = FileInfo {
+    name: name.to_string(),
+    lines: source.lines().map(|l| l.to_string()).collect(),
+    points: Vec::new(),
+    functions: Vec::new()
+  };
+  for e in &block.expressions {
+    collect(e, &mut info);
+  }
+  with(|c| {
+    c.current = c.files.len();
+    c.files.push(info);
+  });
+}
+
+// A call at this position is being evaluated
+pub fn hit(lnum: isize, col: usize) {
+  with(|c| {
+    let key = (c.current, lnum, col);
+    *c.hits.entry(key).or_insert(0) += 1;
+  });
+}
+
+// The function defined at this position is being called
+pub fn function_hit(lnum: isize, col: usize) {
+  with(|c| {
+    let key = (c.current, lnum, col);
+    *c.function_hits.entry(key).or_insert(0) += 1;
+  });
+}
+
+impl Coverage {
+  // Hit count for every line with something on it that could run
+  fn line_hits(&self, file: usize) -> BTreeMap<isize, u64> {
+    let mut rc = BTreeMap::new();
+    for &(lnum, col) in &self.files[file].points {
+      let count = self.hits.get(&(file, lnum, col)).cloned().unwrap_or(0);
+      let line = rc.entry(lnum).or_insert(0);
+      if count > *line {
+        *line = count;
+      }
+    }
+    rc
+  }
+
+  // LCOV wants a unique name per function; anonymous ones (and any name
+  // that's used more than once) get their line added
+  fn function_names(&self, file: usize) -> Vec<String> {
+    let functions = &self.files[file].functions;
+    functions.iter().map(|f| {
+      let dup = functions.iter().filter(|g| g.name == f.name).count() > 1;
+      if f.name.is_empty() {
+        format!("<anonymous>@{}", f.lnum)
+      } else if dup {
+        format!("{}@{}", f.name, f.lnum)
+      } else {
+        f.name.clone()
+      }
+    }).collect()
+  }
+
+  fn function_count(&self, file: usize, f: &Function) -> u64 {
+    self.function_hits.get(&(file, f.lnum, f.col)).cloned().unwrap_or(0)
+  }
+
+  fn report(&self, out: &mut dyn Write) -> io::Result<()> {
+    for (n, info) in self.files.iter().enumerate() {
+      let lines = self.line_hits(n);
+      let run = lines.values().filter(|c| **c > 0).count();
+      let called = info.functions.iter().filter(|f| self.function_count(n, f) > 0).count();
+      let percent = if lines.is_empty() { 100.0 } else { run as f64 * 100.0 / lines.len() as f64 };
+      writeln!(out, "\ncoverage for {}: {:.1}% of lines ({}/{}), {}/{} functions called",
+               info.name, percent, run, lines.len(), called, info.functions.len())?;
+      for (i, text) in info.lines.iter().enumerate() {
+        let count = match lines.get(&(i as isize + 1)) {
+          Some(0) => "#####".to_string(),
+          Some(c) => c.to_string(),
+          None => "-".to_string(),
+        };
+        writeln!(out, "{:>9}:{:>5}: {}", count, i + 1, text)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn lcov(&self, out: &mut dyn Write) -> io::Result<()> {
+    for (n, info) in self.files.iter().enumerate() {
+      writeln!(out, "TN:\nSF:{}", info.name)?;
+      let names = self.function_names(n);
+      for (f, name) in info.functions.iter().zip(&names) {
+        writeln!(out, "FN:{},{}", f.lnum, name)?;
+      }
+      let mut called = 0;
+      for (f, name) in info.functions.iter().zip(&names) {
+        let count = self.function_count(n, f);
+        if count > 0 {
+          called += 1;
+        }
+        writeln!(out, "FNDA:{},{}", count, name)?;
+      }
+      writeln!(out, "FNF:{}\nFNH:{}", info.functions.len(), called)?;
+      let lines = self.line_hits(n);
+      for (lnum, count) in &lines {
+        writeln!(out, "DA:{},{}", lnum, count)?;
+      }
+      writeln!(out, "LF:{}\nLH:{}", lines.len(), lines.values().filter(|c| **c > 0).count())?;
+      writeln!(out, "end_of_record")?;
+    }
+    Ok(())
+  }
+}
+
+// Stop counting, then print the report (annotated source with hit counts,
+// or ##### for lines that never ran, like gcov) on stderr and write the LCOV
+// file, if they were asked for
+pub fn stop() {
+  ACTIVE.with(|a| a.set(false));
+  let coverage = match COVERAGE.with(|c| c.borrow_mut().take()) {
+    Some(coverage) => coverage,
+    None => return,
+  };
+  if coverage.options.report {
+    if let Err(e) = coverage.report(&mut io::stderr()) {
+      eprintln!("unable to write coverage report: {}", e);
+    }
+  }
+  if let Some(ref filename) = coverage.options.lcov {
+    let written = File::create(filename).and_then(|f| {
+      let mut out = BufWriter::new(f);
+      coverage.lcov(&mut out)?;
+      out.flush()
+    });
+    if let Err(e) = written {
+      eprintln!("unable to write coverage data to {}: {}", filename, e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use evaluator;
+  use parser;
+  use tokenizer;
+
+  use encoding::ListEval;
+
+  // inc gets called twice, unused never does
+  const SOURCE: &str = "inc:+[car[_] 1];;\nunused:car[_];;\ninc[1];\ninc[2];\n";
+
+  fn run() -> Coverage {
+    start(Options { report: false, lcov: None });
+    let block = parser::parse(&tokenizer::tokenize(SOURCE).ok().unwrap()).ok().unwrap();
+    add_file("test.cry", SOURCE, &block);
+    evaluator::evaluate(&block, &ListEval { items: Vec::new() });
+    ACTIVE.with(|a| a.set(false));
+    COVERAGE.with(|c| c.borrow_mut().take()).unwrap()
+  }
+
+  #[test]
+  fn line_hits() {
+    let hits: Vec<(isize, u64)> = run().line_hits(0).into_iter().collect();
+    assert_eq!(hits, vec![(1, 2), (2, 0), (3, 1), (4, 1)]);
+  }
+
+  #[test]
+  fn report() {
+    let mut out = Vec::new();
+    run().report(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "
+coverage for test.cry: 75.0% of lines (3/4), 1/2 functions called
+        2:    1: inc:+[car[_] 1];;
+    #####:    2: unused:car[_];;
+        1:    3: inc[1];
+        1:    4: inc[2];
+");
+  }
+
+  #[test]
+  fn lcov() {
+    let mut out = Vec::new();
+    run().lcov(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "\
+TN:
+SF:test.cry
+FN:1,inc
+FN:2,unused
+FNDA:2,inc
+FNDA:0,unused
+FNF:2
+FNH:1
+DA:1,2
+DA:2,0
+DA:3,1
+DA:4,1
+LF:4
+LH:3
+end_of_record
+");
+  }
+}
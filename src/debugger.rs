@@ -0,0 +1,398 @@
+// Step debugger.  Execution pauses when a call is entered, either because of
+// a breakpoint (on a function name, or on a line) or because we're stepping,
+// and then takes commands (see HELP) until told to carry on.
+//
+// Commands are read the same way the program reads input, so a program that
+// reads from stdin shares it with the debugger
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::process;
+
+use host;
+use parser;
+use tokenizer;
+
+use encoding::Call;
+use encoding::Scope;
+use encoding::Evaluation;
+
+const HELP: &str = "\
+  s, step           run until the next call (stepping into this one)
+  n, next           run until the next call at this depth or above
+  o, out            run until the current function returns
+  c, continue       run until the next breakpoint
+  b, break [<name> | <line>]
+                    set a breakpoint on calls to a function or on a line
+                    (with no argument, list breakpoints)
+  d, delete <n>     delete breakpoint n
+  w, where          show the calls in progress
+  scope             show the scope stack: each level's parameter and
+                    definitions (_ is the innermost)
+  p, print <code>   evaluate code in the current scope
+  l, list           show the source around the current line
+  h, help           show this message
+  q, quit           stop the program
+An empty line repeats the last command";
+
+#[derive(Clone, PartialEq)]
+pub enum Breakpoint {
+  Name(String),
+  Line(isize)
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+  Continue,
+  Into,
+  // Pause at the next call at this depth or shallower
+  Over(usize)
+}
+
+struct Debugger {
+  breakpoints: Vec<Breakpoint>,
+  mode: Mode,
+  // Calls in progress (id, line, and whether it's a call to a definition
+  // rather than a primitive)
+  stack: Vec<(String, isize, bool)>,
+  // Line of the last call entered, so line breakpoints only stop once when
+  // there are several calls on the line
+  last_line: isize,
+  lines: Vec<String>,
+  last_command: String
+}
+
+thread_local! {
+  static ACTIVE: Cell<bool> = const { Cell::new(false) };
+
+  static DEBUGGER: RefCell<Option<Debugger>> = const { RefCell::new(None) };
+}
+
+// Read a breakpoint from the command line or a break command
+pub fn breakpoint(s: &str) -> Breakpoint {
+  match s.parse::<isize>() {
+    Ok(n) => Breakpoint::Line(n),
+    Err(_) => Breakpoint::Name(s.to_string()),
+  }
+}
+
+fn describe(b: &Breakpoint) -> String {
+  match *b {
+    Breakpoint::Name(ref id) => format!("calls to {}", id),
+    Breakpoint::Line(n) => format!("line {}", n),
+  }
+}
+
+// Turn the debugger on for the program with this source.  With no
+// breakpoints, it stops at the first call
+pub fn start(source: &str, breakpoints: Vec<Breakpoint>) {
+  let mode = if breakpoints.is_empty() { Mode::Into } else { Mode::Continue };
+  DEBUGGER.with(|d| *d.borrow_mut() = Some(Debugger {
+    breakpoints,
+    mode,
+    stack: Vec::new(),
+    last_line: 0,
+    lines: source.lines().map(|l| l.to_string()).collect(),
+    last_command: "step".to_string()
+  }));
+  ACTIVE.with(|a| a.set(true));
+  println!("curry debugger; type help for commands");
+}
+
+pub fn active() -> bool {
+  ACTIVE.with(|a| a.get())
+}
+
+fn with<T, F: FnOnce(&mut Debugger) -> T>(f: F) -> Option<T> {
+  DEBUGGER.with(|d| d.borrow_mut().as_mut().map(f))
+}
+
+// Called when a call starts, before its arguments are evaluated
+pub fn enter(call: &Call, scope: &mut Vec<Scope>) {
+  let defined = call.id == "," || scope.iter().any(|s| s.bindings.contains_key(&call.id));
+  let reason = with(|d| {
+    let depth = d.stack.len();
+    let line_changed = d.last_line != call.lnum;
+    d.stack.push((call.id.clone(), call.lnum, defined));
+    d.last_line = call.lnum;
+    for (n, b) in d.breakpoints.iter().enumerate() {
+      let hit = match *b {
+        Breakpoint::Name(ref id) => *id == call.id,
+        Breakpoint::Line(line) => line == call.lnum && line_changed,
+      };
+      if hit {
+        return Some(format!("breakpoint {} ({}), ", n + 1, describe(b)));
+      }
+    }
+    let stop = match d.mode {
+      Mode::Continue => false,
+      Mode::Into => true,
+      Mode::Over(target) => depth <= target,
+    };
+    if stop { Some("".to_string()) } else { None }
+  });
+  if let Some(Some(reason)) = reason {
+    println!("stopped at {}line {}, calling {}: {}", reason, call.lnum, call.id,
+             current_line(call.lnum));
+    pause(scope);
+  }
+}
+
+// Called when a call finishes; shows the result when we're stepping over (or
+// out of) it
+pub fn leave(call: &Call, result: &Evaluation) {
+  let show = with(|d| {
+    d.stack.pop();
+    match d.mode {
+      Mode::Over(target) => d.stack.len() == target,
+      _ => false,
+    }
+  });
+  if show == Some(true) {
+    println!("{} returned {}", call.id, result);
+  }
+}
+
+fn current_line(lnum: isize) -> String {
+  with(|d| {
+    if lnum >= 1 {
+      d.lines.get(lnum as usize - 1).map(|l| l.trim().to_string()).unwrap_or_default()
+    } else {
+      "".to_string()
+    }
+  }).unwrap_or_default()
+}
+
+fn show_scope(scope: &[Scope]) {
+  for (n, s) in scope.iter().rev().enumerate() {
+    let mut names: Vec<&str> = s.bindings.keys().map(|k| k.as_str()).collect();
+    names.sort();
+    let defines = if names.is_empty() {
+      "".to_string()
+    } else {
+      format!("  (defines {})", names.join(" "))
+    };
+    println!("  {} = {}{}", "_".repeat(n + 1), Evaluation::List(s.param.clone()),
+             defines);
+  }
+}
+
+fn show_stack() {
+  let stack = with(|d| d.stack.clone()).unwrap_or_default();
+  for (n, &(ref id, lnum, _)) in stack.iter().rev().enumerate() {
+    println!("  #{} {} (line {})", n, id, lnum);
+  }
+}
+
+fn show_source() {
+  let (line, lines) = match with(|d| (d.last_line, d.lines.clone())) {
+    Some(x) => x,
+    None => return,
+  };
+  let from = (line - 5).max(1);
+  let to = (line + 5).min(lines.len() as isize);
+  for n in from..(to + 1) {
+    let marker = if n == line { "=>" } else { "  " };
+    println!("{} {:4} {}", marker, n, lines[n as usize - 1]);
+  }
+}
+
+// Evaluate code right where we're stopped.  Anything it defines is dropped
+// again afterwards, so it doesn't leak into the program
+fn print(code: &str, scope: &mut Vec<Scope>) {
+  let mut source = code.trim().to_string();
+  if !source.ends_with(';') {
+    source.push(';');
+  }
+  source.push('\n');
+  let block = match tokenizer::tokenize(&source).and_then(|t| parser::parse(&t)) {
+    Ok(block) => block,
+    Err(e) => {
+      println!("{}", e);
+      return;
+    },
+  };
+  let before: Vec<String> = match scope.last() {
+    Some(s) => s.bindings.keys().cloned().collect(),
+    None => Vec::new(),
+  };
+  for e in &block.expressions {
+    println!("{}", e.evaluate(scope));
+  }
+  if let Some(s) = scope.last_mut() {
+    s.bindings.retain(|k, _| before.contains(k));
+  }
+}
+
+fn resume(mode: Mode) {
+  with(|d| d.mode = mode);
+}
+
+// Take commands until one of them carries on with the program.  The
+// debugger is switched off meanwhile, so print doesn't stop anywhere
+fn pause(scope: &mut Vec<Scope>) {
+  ACTIVE.with(|a| a.set(false));
+  let depth = with(|d| d.stack.len() - 1).unwrap_or(0);
+  loop {
+    print!("(debug) ");
+    io::stdout().flush().unwrap_or(());
+    let line = match host::read_line() {
+      Ok(Some(line)) => line,
+      _ => {
+        // Nobody left to ask, so just let the program finish
+        println!();
+        with(|d| d.breakpoints.clear());
+        resume(Mode::Continue);
+        break;
+      },
+    };
+    let line = if line.trim().is_empty() {
+      with(|d| d.last_command.clone()).unwrap_or_default()
+    } else {
+      with(|d| d.last_command = line.clone());
+      line
+    };
+    let line = line.trim();
+    let (command, arg) = match line.find(' ') {
+      Some(n) => (&line[..n], line[n + 1..].trim()),
+      None => (line, ""),
+    };
+    match command {
+      "s" | "step" => {
+        resume(Mode::Into);
+        break;
+      },
+      "n" | "next" => {
+        resume(Mode::Over(depth));
+        break;
+      },
+      "o" | "out" => {
+        // Stepping out of a function is stepping over the call to it
+        let caller = with(|d| d.stack[..depth].iter().rposition(|c| c.2)).unwrap_or(None);
+        resume(match caller {
+          Some(n) => Mode::Over(n),
+          None => Mode::Continue,
+        });
+        break;
+      },
+      "c" | "continue" => {
+        resume(Mode::Continue);
+        break;
+      },
+      "b" | "break" => {
+        if arg.is_empty() {
+          let breakpoints = with(|d| d.breakpoints.clone()).unwrap_or_default();
+          if breakpoints.is_empty() {
+            println!("no breakpoints");
+          }
+          for (n, b) in breakpoints.iter().enumerate() {
+            println!("  {}: {}", n + 1, describe(b));
+          }
+        } else {
+          let b = breakpoint(arg);
+          let n = with(|d| {
+            d.breakpoints.push(b.clone());
+            d.breakpoints.len()
+          }).unwrap_or(0);
+          println!("breakpoint {}: {}", n, describe(&b));
+        }
+      },
+      "d" | "delete" => {
+        let removed = arg.parse::<usize>().ok().and_then(|n| with(|d| {
+          if n >= 1 && n <= d.breakpoints.len() {
+            Some(d.breakpoints.remove(n - 1))
+          } else {
+            None
+          }
+        }).unwrap_or(None));
+        match removed {
+          Some(b) => println!("deleted breakpoint on {}", describe(&b)),
+          None => println!("no breakpoint {}", arg),
+        }
+      },
+      "w" | "where" => show_stack(),
+      "scope" => show_scope(scope),
+      "p" | "print" => print(arg, scope),
+      "l" | "list" => show_source(),
+      "h" | "help" => println!("{}", HELP),
+      "q" | "quit" => process::exit(0),
+      _ => println!("unknown command: {} (try help)", command),
+    }
+  }
+  ACTIVE.with(|a| a.set(true));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  use evaluator;
+  use encoding::ListEval;
+
+  const SOURCE: &str = "inc:+[car[_] 1];;\ninc[1];\ninc[2];\n";
+
+  // Run SOURCE under the debugger with these commands as input, and give
+  // back the breakpoints at the end and the first line nobody read
+  fn debug(breakpoints: &[&str], commands: &str) -> (Vec<Breakpoint>, Option<String>) {
+    host::set_input(Box::new(Cursor::new(commands.to_string() + "left over\n")));
+    start(SOURCE, breakpoints.iter().map(|b| breakpoint(b)).collect());
+    let block = parser::parse(&tokenizer::tokenize(SOURCE).ok().unwrap()).ok().unwrap();
+    evaluator::evaluate(&block, &ListEval { items: Vec::new() });
+    ACTIVE.with(|a| a.set(false));
+    let breakpoints = DEBUGGER.with(|d| d.borrow_mut().take()).unwrap().breakpoints;
+    (breakpoints, host::read_line().unwrap())
+  }
+
+  #[test]
+  fn parse_breakpoints() {
+    assert!(breakpoint("12") == Breakpoint::Line(12));
+    assert!(breakpoint("car") == Breakpoint::Name("car".to_string()));
+    assert_eq!(describe(&breakpoint("12")), "line 12");
+    assert_eq!(describe(&breakpoint("car")), "calls to car");
+  }
+
+  #[test]
+  fn stops_at_each_call_to_a_name() {
+    // car gets called twice, so it takes two continues
+    let (_, left) = debug(&["car"], "c\nc\n");
+    assert_eq!(left.as_deref(), Some("left over"));
+  }
+
+  #[test]
+  fn stops_once_per_line() {
+    // Line 1 has both car and + on it, but each visit only stops once
+    let (_, left) = debug(&["1"], "c\nc\n");
+    assert_eq!(left.as_deref(), Some("left over"));
+  }
+
+  #[test]
+  fn stepping() {
+    // With no breakpoints it stops straight away, then steps through
+    // inc, car and + on the first line before continuing
+    let (_, left) = debug(&[], "s\n\nc\n");
+    assert_eq!(left.as_deref(), Some("left over"));
+    // next steps over inc[1] to inc[2]
+    let (_, left) = debug(&[], "n\nc\n");
+    assert_eq!(left.as_deref(), Some("left over"));
+  }
+
+  #[test]
+  fn set_and_delete_breakpoints() {
+    // Stops at the first call, then again at line 3 but not at car
+    let (breakpoints, left) = debug(&[], "b car\nb 3\nd 1\nd 5\nc\nc\n");
+    assert!(breakpoints == vec![Breakpoint::Line(3)]);
+    assert_eq!(left.as_deref(), Some("left over"));
+  }
+
+  #[test]
+  fn end_of_input() {
+    // left over is an unknown command, and after that there are no commands
+    // left, so it runs to the end
+    let (breakpoints, left) = debug(&["car"], "");
+    assert!(breakpoints.is_empty());
+    assert_eq!(left, None);
+  }
+}
@@ -0,0 +1,56 @@
+// Human-readable dumps of what the tokenizer and parser produce
+
+use encoding::Token;
+use encoding::Block;
+use encoding::Expression;
+use encoding::List;
+
+pub fn tokens(tokens: &[Token]) -> String {
+  let mut lines = Vec::new();
+  for t in tokens {
+    lines.push(format!("{:?}", t));
+  }
+  lines.join("\n")
+}
+
+fn indent(depth: usize) -> String {
+  "  ".repeat(depth)
+}
+
+fn list_tree(list: &List, depth: usize, lines: &mut Vec<String>) {
+  for i in &list.items {
+    expression_tree(i, depth, lines);
+  }
+}
+
+fn expression_tree(exp: &Expression, depth: usize, lines: &mut Vec<String>) {
+  match *exp {
+    Expression::List(ref list) => {
+      lines.push(format!("{}LIST", indent(depth)));
+      list_tree(list, depth + 1, lines);
+    },
+    Expression::Call(ref call) => {
+      lines.push(format!("{}CALL:{}", indent(depth), call.id));
+      list_tree(&call.param, depth + 1, lines);
+    },
+    Expression::Definition(ref def) => {
+      lines.push(format!("{}DEFINITION:{}", indent(depth), def.id));
+      block_tree(&def.block, depth + 1, lines);
+    },
+    // Scalars already print nicely enough
+    ref scalar => lines.push(format!("{}{:?}", indent(depth), scalar)),
+  }
+}
+
+fn block_tree(block: &Block, depth: usize, lines: &mut Vec<String>) {
+  for e in &block.expressions {
+    expression_tree(e, depth, lines);
+  }
+}
+
+// One node per line, children indented under their parents
+pub fn tree(block: &Block) -> String {
+  let mut lines = Vec::new();
+  block_tree(block, 0, &mut lines);
+  lines.join("\n")
+}
@@ -2,21 +2,32 @@
 
 use std::collections::HashMap;
 
+use bigint::BigInt;
+use symbol::Symbol;
+
 pub struct LineLookup {
   pub lnums: Vec<usize>,
-  pub lines: Vec<String>
+  pub lines: Vec<String>,
+  pub starts: Vec<usize>
 }
 
 pub struct Token {
   pub value: TokenValue,
   pub lnum: isize,
+  pub col: usize,
+  pub line: String
+}
+
+pub struct ParseError {
+  pub msg: String,
+  pub lnum: isize,
   pub line: String
 }
 
 pub enum TokenValue {
   Colon, Semicolon, OpenBracket, CloseBracket,
-  ID(String), Integer(i64), Float(f64), String(String),
-  True, False, EOF
+  ID(String), Integer(i64), BigInt(BigInt), Float(f64), String(String),
+  Symbol(Symbol), True, False, EOF
 }
 
 pub struct Block {
@@ -24,8 +35,8 @@ pub struct Block {
 }
 
 pub enum Expression {
-  True, False, Integer(i64), Float(f64), String(String), List(List),
-  Call(Call), Definition(Definition)
+  True, False, Integer(i64), BigInt(BigInt), Float(f64), String(String),
+  Symbol(Symbol), List(List), Call(Call), Definition(Definition)
 }
 
 // On a high level, we treat lists like S-expressions, but due to some major
@@ -39,12 +50,16 @@ pub struct List {
 
 pub struct Call {
   pub id: String,
-  pub param: List
+  pub param: List,
+  pub lnum: isize,
+  pub col: usize
 }
 
 pub struct Definition {
   pub id: String,
-  pub block: Block
+  pub block: Block,
+  pub lnum: isize,
+  pub col: usize
 }
 
 pub struct Scope {
@@ -53,8 +68,8 @@ pub struct Scope {
 }
 
 pub enum Evaluation {
-  True, False, Integer(i64), Float(f64), String(String), List(ListEval),
-  Function(Function), Exception(Exception)
+  True, False, Integer(i64), BigInt(BigInt), Float(f64), String(String),
+  Symbol(Symbol), List(ListEval), Function(Function), Exception(Exception)
 }
 
 pub struct ListEval {
@@ -62,7 +77,10 @@ pub struct ListEval {
 }
 
 pub struct Function {
-  pub block: Block
+  pub block: Block,
+  // Where it was defined
+  pub lnum: isize,
+  pub col: usize
 }
 
 pub struct Exception {
@@ -73,5 +91,6 @@ pub struct Exception {
 
 pub enum ExceptionType {
   Return, Error, ArgError, ParseError, TypeError, TypeMismatch, DivByZero,
-  RuntimeError, UndefError, RedefError
+  RuntimeError, UndefError, RedefError, IOError, AssertionError,
+  Overflow, DomainError
 }
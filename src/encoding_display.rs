@@ -5,6 +5,7 @@ use std::fmt::Error;
 
 use encoding::Token;
 use encoding::TokenValue;
+use encoding::ParseError;
 
 use encoding::Expression;
 use encoding::List;
@@ -27,8 +28,10 @@ impl Debug for Token {
       TokenValue::CloseBracket => "CLOSEBRACKET".to_string(),
       TokenValue::ID(ref x) => "ID:".to_string() + &x,
       TokenValue::Integer(ref x) => "INTEGER:".to_string() + &x.to_string(),
+      TokenValue::BigInt(ref x) => "BIGINT:".to_string() + &x.to_string(),
       TokenValue::Float(ref x) => "FLOAT:".to_string() + &x.to_string(),
       TokenValue::String(ref x) => "STRING:".to_string() + &x,
+      TokenValue::Symbol(ref x) => "SYMBOL:".to_string() + &x.name(),
       TokenValue::EOF => "EOF".to_string(),
     };
     s += &format!("/[{}:{}]", self.lnum, self.line);
@@ -36,14 +39,23 @@ impl Debug for Token {
   }
 }
 
+impl Display for ParseError {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    write!(f, "--- Parse error on line {} :\n--- {} :\n\"{}\"",
+           self.lnum, self.msg, self.line)
+  }
+}
+
 impl Debug for Expression {
   fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
     let s = match self {
       &Expression::True => "TRUE".to_string(),
       &Expression::False => "FALSE".to_string(),
       &Expression::Integer(ref x) => "INTEGER:".to_string() + &x.to_string(),
+      &Expression::BigInt(ref x) => "BIGINT:".to_string() + &x.to_string(),
       &Expression::Float(ref x) => "FLOAT:".to_string() + &x.to_string(),
       &Expression::String(ref x) => "STRING:".to_string() + &x,
+      &Expression::Symbol(ref x) => "SYMBOL:".to_string() + &x.name(),
       &Expression::List(ref x) => {
         format!("{:?}", x)
       },
@@ -89,8 +101,10 @@ impl Debug for Evaluation {
       &Evaluation::True => "TRUE".to_string(),
       &Evaluation::False => "FALSE".to_string(),
       &Evaluation::Integer(ref x) => "INTEGER:".to_string() + &x.to_string(),
+      &Evaluation::BigInt(ref x) => "BIGINT:".to_string() + &x.to_string(),
       &Evaluation::Float(ref x) => "FLOAT:".to_string() + &x.to_string(),
       &Evaluation::String(ref x) => "STRING:".to_string() + &x,
+      &Evaluation::Symbol(ref x) => "SYMBOL:".to_string() + &x.name(),
       &Evaluation::List(ref x) => {
         format!("{:?}", x)
       },
@@ -141,8 +155,10 @@ impl Display for Evaluation {
       &Evaluation::True => "true".to_string(),
       &Evaluation::False => "false".to_string(),
       &Evaluation::Integer(x) => x.to_string(),
+      &Evaluation::BigInt(ref x) => x.to_string(),
       &Evaluation::Float(x) => x.to_string(),
       &Evaluation::String(ref x) => format!("\"{}\"", x),
+      &Evaluation::Symbol(x) => format!("'{}", x.name()),
       &Evaluation::List(ref x) => {
         let mut s2 = "[".to_string();
         let mut items = Vec::new();
@@ -198,6 +214,10 @@ impl Display for ExceptionType {
       &ExceptionType::RuntimeError => "runtime error".to_string(),
       &ExceptionType::UndefError => "undefined function".to_string(),
       &ExceptionType::RedefError => "redefinition error".to_string(),
+      &ExceptionType::IOError => "io error".to_string(),
+      &ExceptionType::AssertionError => "assertion error".to_string(),
+      &ExceptionType::Overflow => "overflow error".to_string(),
+      &ExceptionType::DomainError => "domain error".to_string(),
     };
     write!(f, "{}", s)
   }
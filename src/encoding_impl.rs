@@ -1,7 +1,11 @@
 use std::collections::HashMap;
 
+use coverage;
+use debugger;
 use evaluator;
 use primitives;
+use profile;
+use trace;
 
 use encoding::Block;
 use encoding::Expression;
@@ -36,11 +40,27 @@ impl List {
 
 impl Call {
   pub fn evaluate(&self, scope: &mut Vec<Scope>) -> Evaluation {
+    if debugger::active() {
+      debugger::enter(self, scope);
+    }
+    let rc = self.call(scope);
+    if debugger::active() {
+      debugger::leave(self, &rc);
+    }
+    rc
+  }
+
+  fn call(&self, scope: &mut Vec<Scope>) -> Evaluation {
     for x in (0..scope.len()).rev() {
       if scope[x].bindings.contains_key(&self.id) {
         let binding = scope[x].bindings[&self.id].clone();
         let eval = self.param.evaluate(scope);
-        return binding.block.evaluate(scope, &eval, &self.id);
+        if coverage::active() {
+          coverage::function_hit(binding.lnum, binding.col);
+        }
+        return self.invoke(scope, eval, |scope, eval| {
+          binding.block.evaluate(scope, &eval, &self.id)
+        });
       }
     }
 
@@ -57,7 +77,12 @@ impl Call {
           match self.param.items[1] {
             Expression::List(ref list) => {
               let elist = list.evaluate(scope);
-              return func.block.evaluate(scope, &elist, &self.id);
+              if coverage::active() {
+                coverage::function_hit(func.lnum, func.col);
+              }
+              return self.invoke(scope, elist, |scope, elist| {
+                func.block.evaluate(scope, &elist, &self.id)
+              });
             },
             _ => {
               return evaluator::exception(ExceptionType::TypeError, &self.id,
@@ -92,15 +117,42 @@ impl Call {
         },
         _ => {
           // Try low-level system functions
-          primitives::system_functions(self.id.clone(),
-                                       self.param.evaluate(scope))
+          let eval = self.param.evaluate(scope);
+          self.invoke(scope, eval, |_, eval| {
+            primitives::system_functions(self.id.clone(), eval)
+          })
         }
       }
     }
   }
 
+  // Everything that runs with its arguments evaluated goes through here, so
+  // tracing and profiling see the arguments and the result
+  fn invoke<F>(&self, scope: &mut Vec<Scope>, args: ListEval, run: F) -> Evaluation
+    where F: FnOnce(&mut Vec<Scope>, ListEval) -> Evaluation {
+    let (tracing, profiling) = (trace::active(), profile::active());
+    if !tracing && !profiling {
+      return run(scope, args);
+    }
+    if tracing {
+      trace::enter(&self.id, &args);
+    }
+    if profiling {
+      profile::enter(&self.id, args.items.len());
+    }
+    let rc = run(scope, args);
+    if profiling {
+      profile::leave();
+    }
+    if tracing {
+      trace::leave(&self.id, &rc);
+    }
+    rc
+  }
+
   pub fn clone(&self) -> Call {
-    Call { id: self.id.clone(), param: self.param.clone() }
+    Call { id: self.id.clone(), param: self.param.clone(), lnum: self.lnum,
+           col: self.col }
   }
 }
 
@@ -108,12 +160,16 @@ impl Definition {
   pub fn evaluate(&self, scope: &mut Vec<Scope>) -> Evaluation {
     let last = scope.last_mut();
     if let Some(s) = last {
+      let func = Function { block: self.block.clone(), lnum: self.lnum, col: self.col };
+      // Anonymous functions don't get bound to anything
+      if self.id.is_empty() {
+        return Evaluation::Function(func);
+      }
       if s.bindings.contains_key(&self.id) {
         return evaluator::exception(ExceptionType::RedefError, &"".to_string(),
                                     format!("attempt to redefine {}",
                                             self.id));
       }
-      let func = Function { block: self.block.clone() };
       s.bindings.insert(self.id.clone(), func.clone());
       Evaluation::Function(func)
     } else {
@@ -122,18 +178,26 @@ impl Definition {
   }
 
   pub fn clone(&self) -> Definition {
-    Definition { id: self.id.clone(), block: self.block.clone() }
+    Definition { id: self.id.clone(), block: self.block.clone(),
+                 lnum: self.lnum, col: self.col }
   }
 }
 
 impl Expression {
   pub fn evaluate(&self, scope: &mut Vec<Scope>) -> Evaluation {
+    if coverage::active() {
+      if let Expression::Call(ref call) = *self {
+        coverage::hit(call.lnum, call.col);
+      }
+    }
     match self {
       &Expression::True => Evaluation::True,
       &Expression::False => Evaluation::False,
       &Expression::Integer(x) => Evaluation::Integer(x),
+      &Expression::BigInt(ref x) => Evaluation::BigInt(x.clone()),
       &Expression::Float(x) => Evaluation::Float(x),
       &Expression::String(ref s) => Evaluation::String(s.clone()),
+      &Expression::Symbol(x) => Evaluation::Symbol(x),
       &Expression::List(ref list) => Evaluation::List(list.evaluate(scope)),
       &Expression::Call(ref call) => call.evaluate(scope),
       &Expression::Definition(ref def) => def.evaluate(scope),
@@ -145,8 +209,10 @@ impl Expression {
       &Expression::True => Expression::True,
       &Expression::False => Expression::False,
       &Expression::Integer(x) => Expression::Integer(x),
+      &Expression::BigInt(ref x) => Expression::BigInt(x.clone()),
       &Expression::Float(x) => Expression::Float(x),
       &Expression::String(ref s) => Expression::String(s.clone()),
+      &Expression::Symbol(x) => Expression::Symbol(x),
       &Expression::List(ref list) => Expression::List(list.clone()),
       &Expression::Call(ref call) => Expression::Call(call.clone()),
       &Expression::Definition(ref def) => Expression::Definition(def.clone()),
@@ -202,8 +268,10 @@ impl Evaluation {
       &Evaluation::True => Evaluation::True,
       &Evaluation::False => Evaluation::False,
       &Evaluation::Integer(x) => Evaluation::Integer(x),
+      &Evaluation::BigInt(ref x) => Evaluation::BigInt(x.clone()),
       &Evaluation::Float(x) => Evaluation::Float(x),
       &Evaluation::String(ref s) => Evaluation::String(s.clone()),
+      &Evaluation::Symbol(x) => Evaluation::Symbol(x),
       &Evaluation::List(ref list) => Evaluation::List(list.clone()),
       &Evaluation::Exception(ref e) => Evaluation::Exception(e.clone()),
       &Evaluation::Function(ref func) => Evaluation::Function(func.clone()),
@@ -223,7 +291,7 @@ impl ListEval {
 
 impl Function {
   pub fn clone(&self) -> Function {
-    Function { block: self.block.clone() }
+    Function { block: self.block.clone(), lnum: self.lnum, col: self.col }
   }
 }
 
@@ -270,6 +338,10 @@ impl ExceptionType {
       &ExceptionType::RuntimeError => ExceptionType::RuntimeError,
       &ExceptionType::UndefError => ExceptionType::UndefError,
       &ExceptionType::RedefError => ExceptionType::RedefError,
+      &ExceptionType::IOError => ExceptionType::IOError,
+      &ExceptionType::AssertionError => ExceptionType::AssertionError,
+      &ExceptionType::Overflow => ExceptionType::Overflow,
+      &ExceptionType::DomainError => ExceptionType::DomainError,
     }
   }
 }
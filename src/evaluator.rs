@@ -1,5 +1,7 @@
 // Evaluate parsed stuff
 
+use host;
+
 use encoding::Block;
 use encoding::Evaluation;
 use encoding::ListEval;
@@ -14,16 +16,18 @@ pub fn exception(flavor: ExceptionType, id: &String, msg: String) ->
                         &Evaluation::String(format!("{} : {}", id, msg))))
 }
 
-pub fn evaluate(block: &Block) {
+// The main program gets whatever the host passes in (e.g., command line
+// arguments) as its parameter
+pub fn evaluate(block: &Block, param: &ListEval) {
   let mut scope = Vec::new();
   let result = block.evaluate(
     &mut scope,
-    &ListEval{ items: Vec::new() },
+    param,
     &"[main program]".to_string()
   );
   match &result {
     &Evaluation::Exception(ref e) => {
-      println!("{}", e);
+      host::write_line(&e.to_string());
     },
     _ => {
       // not an error, do nothing
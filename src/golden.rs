@@ -0,0 +1,265 @@
+// Golden output tests: run each script and compare everything it prints
+// (including the uncaught exception report, if any) with the contents of the
+// sibling .expected file, e.g. foo.cry -> foo.expected
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+
+use evaluator;
+use host;
+use parser;
+use tokenizer;
+use testing;
+use testing::Summary;
+
+use encoding::ListEval;
+
+const CONTEXT: usize = 3;
+
+fn expected_path(filename: &str) -> PathBuf {
+  Path::new(filename).with_extension("expected")
+}
+
+// Run the script the same way the command line would, but keep the output
+fn run_script(filename: &str) -> Result<String, String> {
+  let mut source = String::new();
+  if let Err(e) = File::open(filename)
+    .and_then(|mut f| f.read_to_string(&mut source)) {
+    return Err(format!("unable to read file: {}", e));
+  }
+  if !source.ends_with('\n') {
+    source.push('\n');
+  }
+  host::set_script_path(Some(filename.to_string()));
+  host::capture_output();
+  let parsed = tokenizer::tokenize(&source)
+    .and_then(|tokens| parser::parse(&tokens));
+  match parsed {
+    Ok(block) => evaluator::evaluate(&block, &ListEval { items: Vec::new() }),
+    Err(e) => host::write_line(&e.to_string()),
+  }
+  Ok(host::take_output())
+}
+
+// Longest common subsequence of lines, turned into a list of edits: ' ' for
+// lines in both, '-' for lines only in old, '+' for lines only in new
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+  let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      lcs[i][j] = if old[i] == new[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+  let mut rc = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < old.len() || j < new.len() {
+    if i < old.len() && j < new.len() && old[i] == new[j] {
+      rc.push((' ', old[i]));
+      i += 1;
+      j += 1;
+    } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+      rc.push(('-', old[i]));
+      i += 1;
+    } else {
+      rc.push(('+', new[j]));
+      j += 1;
+    }
+  }
+  rc
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+  // By convention, an empty range starts at the line before it
+  if count == 0 {
+    format!("{},0", start)
+  } else {
+    format!("{},{}", start + 1, count)
+  }
+}
+
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) ->
+  String {
+  let old_lines: Vec<&str> = old.lines().collect();
+  let new_lines: Vec<&str> = new.lines().collect();
+  let ops = edits(&old_lines, &new_lines);
+
+  let mut rc = format!("--- {}\n+++ {}\n", old_name, new_name);
+  let mut index = 0;
+  // Line numbers (0-based) in old and new at ops[index]
+  let (mut old_line, mut new_line) = (0, 0);
+  while index < ops.len() {
+    if ops[index].0 == ' ' {
+      old_line += 1;
+      new_line += 1;
+      index += 1;
+      continue;
+    }
+    // Found a change; back up for context, then keep going until we've seen
+    // enough unchanged lines in a row to close the hunk
+    let start = index.saturating_sub(CONTEXT);
+    let back = index - start;
+    let (hunk_old, hunk_new) = (old_line - back, new_line - back);
+    let mut end = index;
+    let mut quiet = 0;
+    while end < ops.len() && quiet <= CONTEXT * 2 {
+      if ops[end].0 == ' ' {
+        quiet += 1;
+      } else {
+        quiet = 0;
+      }
+      end += 1;
+    }
+    if quiet > CONTEXT {
+      end -= quiet - CONTEXT;
+    }
+    let old_count = ops[start..end].iter().filter(|op| op.0 != '+').count();
+    let new_count = ops[start..end].iter().filter(|op| op.0 != '-').count();
+    rc += &format!("@@ -{} +{} @@\n", hunk_range(hunk_old, old_count),
+                   hunk_range(hunk_new, new_count));
+    for op in &ops[start..end] {
+      rc += &format!("{}{}\n", op.0, op.1);
+    }
+    old_line = hunk_old + old_count;
+    new_line = hunk_new + new_count;
+    index = end;
+  }
+  if !ops.iter().any(|op| op.0 != ' ') {
+    rc += "(only line endings differ)\n";
+  }
+  rc
+}
+
+fn check_file(filename: &str, bless: bool, summary: &mut Summary) {
+  let expected_file = expected_path(filename);
+  let expected_name = expected_file.to_string_lossy().into_owned();
+  let actual = match run_script(filename) {
+    Ok(actual) => actual,
+    Err(e) => {
+      println!("- : {} : {}", filename, e);
+      summary.failed += 1;
+      return;
+    },
+  };
+
+  if bless {
+    match File::create(&expected_file)
+      .and_then(|mut f| f.write_all(actual.as_bytes())) {
+      Ok(_) => {
+        println!("+ : {} : blessed", filename);
+        summary.passed += 1;
+      },
+      Err(e) => {
+        println!("- : {} : unable to write {}: {}", filename, expected_name, e);
+        summary.failed += 1;
+      },
+    }
+    return;
+  }
+
+  let mut expected = String::new();
+  if let Err(e) = File::open(&expected_file)
+    .and_then(|mut f| f.read_to_string(&mut expected)) {
+    println!("- : {} : unable to read {} (use --bless to create it): {}",
+             filename, expected_name, e);
+    summary.failed += 1;
+    return;
+  }
+  if actual == expected {
+    println!("+ : {}", filename);
+    summary.passed += 1;
+  } else {
+    println!("- : {} : output differs", filename);
+    print!("{}", unified_diff(&expected, &actual, &expected_name,
+                              &format!("{} (actual)", filename)));
+    summary.failed += 1;
+  }
+}
+
+pub fn run(paths: &[String], bless: bool) -> Summary {
+  let mut files = Vec::new();
+  for path in paths {
+    testing::collect_files(Path::new(path), &mut files);
+  }
+  let mut summary = Summary { passed: 0, failed: 0 };
+  for f in &files {
+    check_file(f, bless, &mut summary);
+  }
+  println!("\n{} passed, {} failed", summary.passed, summary.failed);
+  summary
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+  use std::fs;
+
+  fn diff(old: &str, new: &str) -> String {
+    unified_diff(old, new, "old", "new")
+  }
+
+  #[test]
+  fn identical() {
+    // Only ever asked for when the text differs, so that must be line endings
+    assert_eq!(diff("a\nb\n", "a\nb\n"), "--- old\n+++ new\n(only line endings differ)\n");
+    assert_eq!(diff("a\r\nb", "a\nb\n"), "--- old\n+++ new\n(only line endings differ)\n");
+  }
+
+  #[test]
+  fn insertion() {
+    assert_eq!(diff("a\nb\n", "a\nx\nb\n"),
+               "--- old\n+++ new\n@@ -1,2 +1,3 @@\n a\n+x\n b\n");
+    assert_eq!(diff("", "x\n"), "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+x\n");
+  }
+
+  #[test]
+  fn deletion() {
+    assert_eq!(diff("a\nx\nb\n", "a\nb\n"),
+               "--- old\n+++ new\n@@ -1,3 +1,2 @@\n a\n-x\n b\n");
+    assert_eq!(diff("x\n", ""), "--- old\n+++ new\n@@ -1,1 +0,0 @@\n-x\n");
+  }
+
+  #[test]
+  fn change_in_the_middle() {
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+    assert_eq!(diff(old, new),
+               "--- old\n+++ new\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n");
+  }
+
+  #[test]
+  fn separate_hunks() {
+    let old: Vec<String> = (1..21).map(|n| n.to_string()).collect();
+    let mut new = old.clone();
+    new[1] = "two".to_string();
+    new[17] = "eighteen".to_string();
+    let rc = diff(&(old.join("\n") + "\n"), &(new.join("\n") + "\n"));
+    let headers: Vec<&str> = rc.lines().filter(|l| l.starts_with("@@")).collect();
+    assert_eq!(headers, vec!["@@ -1,5 +1,5 @@", "@@ -15,6 +15,6 @@"]);
+  }
+
+  #[test]
+  fn bless() {
+    let dir = env::temp_dir().join(format!("curry-golden-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("hello.cry");
+    fs::write(&script, ">>[\"hello\"];\n").unwrap();
+    let paths = vec![script.to_string_lossy().into_owned()];
+
+    // Nothing to compare with until it's blessed
+    assert_eq!(run(&paths, false).failed, 1);
+    assert_eq!(run(&paths, true).passed, 1);
+    assert_eq!(fs::read_to_string(dir.join("hello.expected")).unwrap(), "hello\n");
+    assert_eq!(run(&paths, false).passed, 1);
+
+    fs::write(dir.join("hello.expected"), "goodbye\n").unwrap();
+    assert_eq!(run(&paths, false).failed, 1);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
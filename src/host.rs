@@ -0,0 +1,136 @@
+// Host environment: things the interpreter gets from whoever is running it
+// rather than from the program itself.  Kept in thread-local state so we don't
+// have to drag it through every evaluate call
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+
+thread_local! {
+  static INPUT: RefCell<Box<dyn BufRead>> =
+    RefCell::new(Box::new(BufReader::new(io::stdin())));
+
+  // Programs don't get to touch the filesystem unless the host says so
+  static FILESYSTEM: Cell<bool> = const { Cell::new(false) };
+
+  static SCRIPT: RefCell<Option<String>> = const { RefCell::new(None) };
+
+  // When this is set, output goes here instead of stdout
+  static CAPTURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Replace the source << reads from (stdin unless somebody says otherwise)
+pub fn set_input(input: Box<dyn BufRead>) {
+  INPUT.with(|i| *i.borrow_mut() = input);
+}
+
+// Next line of input without the line ending, or None at EOF
+pub fn read_line() -> io::Result<Option<String>> {
+  INPUT.with(|i| {
+    let mut line = String::new();
+    if i.borrow_mut().read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    if line.ends_with('\n') {
+      line.pop();
+      if line.ends_with('\r') {
+        line.pop();
+      }
+    }
+    Ok(Some(line))
+  })
+}
+
+// Everything left in the input as one string (empty at EOF)
+pub fn read_all() -> io::Result<String> {
+  INPUT.with(|i| {
+    let mut rc = String::new();
+    i.borrow_mut().read_to_string(&mut rc)?;
+    Ok(rc)
+  })
+}
+
+pub fn grant_filesystem(allow: bool) {
+  FILESYSTEM.with(|f| f.set(allow));
+}
+
+pub fn filesystem_allowed() -> bool {
+  FILESYSTEM.with(|f| f.get())
+}
+
+// Path of the program being run, if there is one
+pub fn set_script_path(path: Option<String>) {
+  SCRIPT.with(|s| *s.borrow_mut() = path);
+}
+
+pub fn script_path() -> Option<String> {
+  SCRIPT.with(|s| s.borrow().clone())
+}
+
+// Everything the program prints (>> and uncaught exceptions) comes through here
+pub fn write_line(s: &str) {
+  CAPTURE.with(|c| {
+    match *c.borrow_mut() {
+      Some(ref mut buffer) => {
+        buffer.push_str(s);
+        buffer.push('\n');
+      },
+      None => println!("{}", s),
+    }
+  });
+}
+
+// Start collecting output instead of printing it
+pub fn capture_output() {
+  CAPTURE.with(|c| *c.borrow_mut() = Some(String::new()));
+}
+
+// Stop collecting output, and hand over whatever was collected
+pub fn take_output() -> String {
+  CAPTURE.with(|c| c.borrow_mut().take().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  use evaluator;
+  use parser;
+  use tokenizer;
+
+  use encoding::ListEval;
+
+  // What the program prints when given this input
+  fn run(source: &str, input: &str) -> String {
+    set_input(Box::new(Cursor::new(input.to_string())));
+    capture_output();
+    let block = parser::parse(&tokenizer::tokenize(source).ok().unwrap()).ok().unwrap();
+    evaluator::evaluate(&block, &ListEval { items: Vec::new() });
+    take_output()
+  }
+
+  #[test]
+  fn read_lines() {
+    let source = ">>[string[[<<[] <<[] <<[] <<[]]]];\n";
+    // Line endings are dropped, blank lines are "" and EOF is []
+    assert_eq!(run(source, "one\r\n\ntwo"), "[\"one\" \"\" \"two\" []]\n");
+    assert_eq!(run(source, ""), "[[] [] [] []]\n");
+  }
+
+  #[test]
+  fn read_everything() {
+    let source = ">>[string[[<<[] <<<[] <<<[] <<[]]]];\n";
+    // Whatever's left, line endings and all, then "" at EOF
+    assert_eq!(run(source, "one\ntwo\nthree\n"), "[\"one\" \"two\nthree\n\" \"\" []]\n");
+  }
+
+  #[test]
+  fn no_arguments() {
+    assert!(run(">>[<<[1]];\n", "one\n").contains("expected argument list of length 0"));
+    assert!(run(">>[<<<[1]];\n", "one\n").contains("expected argument list of length 0"));
+  }
+}
@@ -0,0 +1,785 @@
+// Optional static checking: works out what each definition does with its
+// parameter (how many items it needs, what types they have to be) and what it
+// returns, then checks calls against that and against the primitives, so the
+// obvious type and argument count errors show up before anything runs.
+//
+// Parameters are tracked symbolically.  Scoping is dynamic, so __ inside a
+// definition means "whoever called me's parameter"; Elem(m, k) is item k of
+// the parameter m calls up (Elem(0, k) is car[cdr^k[_]]), and Rest(m, n) is
+// that parameter minus its first n items.  At a call site these get replaced
+// with the arguments actually passed (or shifted up one level for the caller)
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error;
+
+use parser;
+use primitives_int;
+use primitives_bits;
+use primitives_type;
+use testing;
+use tokenizer;
+
+use encoding::Block;
+use encoding::Expression;
+use encoding::Call;
+use encoding::Definition;
+use encoding::ParseError;
+
+#[derive(Clone, PartialEq)]
+pub enum Type {
+  Any, Atom, Symbol, Int, Float, Number, String, Function,
+  List(Option<Vec<Type>>),
+  Elem(usize, usize),
+  Rest(usize, usize)
+}
+
+// What a definition needs from its parameters and what it gives back
+#[derive(Clone)]
+pub struct Signature {
+  // Minimum number of items in the parameter m calls up
+  pub min: BTreeMap<usize, usize>,
+  // Required type for item k of the parameter m calls up
+  pub constraints: BTreeMap<(usize, usize), Type>,
+  pub returns: Type
+}
+
+pub struct Problem {
+  pub lnum: isize,
+  pub msg: String
+}
+
+pub struct Report {
+  pub name: String,
+  pub lnum: isize,
+  pub signature: Signature
+}
+
+struct DefInfo<'a> {
+  def: &'a Definition,
+  env: Vec<HashMap<String, usize>>,
+  signature: Option<Signature>,
+  busy: bool
+}
+
+struct Checker<'a> {
+  defs: Vec<DefInfo<'a>>,
+  problems: Vec<Problem>
+}
+
+fn underscores(n: usize) -> String {
+  "_".repeat(n)
+}
+
+impl Display for Type {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    let s = match *self {
+      Type::Any => "any".to_string(),
+      Type::Atom => "atom".to_string(),
+      Type::Symbol => "symbol".to_string(),
+      Type::Int => "int".to_string(),
+      Type::Float => "float".to_string(),
+      Type::Number => "number".to_string(),
+      Type::String => "string".to_string(),
+      Type::Function => "function".to_string(),
+      Type::List(None) => "list".to_string(),
+      Type::List(Some(ref items)) => {
+        let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+        format!("[{}]", items.join(" "))
+      },
+      Type::Elem(m, k) => format!("{}[{}]", underscores(m + 1), k),
+      Type::Rest(m, 0) => underscores(m + 1),
+      Type::Rest(m, n) => format!("{}[{}..]", underscores(m + 1), n),
+    };
+    write!(f, "{}", s)
+  }
+}
+
+impl Signature {
+  fn unknown() -> Signature {
+    Signature { min: BTreeMap::new(), constraints: BTreeMap::new(),
+                returns: Type::Any }
+  }
+}
+
+impl Display for Signature {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    let mut parts = Vec::new();
+    for (m, n) in &self.min {
+      parts.push(format!("{} has at least {} item(s)", underscores(m + 1), n));
+    }
+    for (&(m, k), t) in &self.constraints {
+      parts.push(format!("{} is {}", Type::Elem(m, k), t));
+    }
+    if parts.is_empty() {
+      parts.push("no requirements".to_string());
+    }
+    write!(f, "{}; returns {}", parts.join(", "), self.returns)
+  }
+}
+
+fn is_numeric(t: &Type) -> bool {
+  matches!(*t, Type::Int | Type::Float | Type::Number)
+}
+
+fn is_symbolic(t: &Type) -> bool {
+  matches!(*t, Type::Elem(_, _))
+}
+
+// Could a value of type actual be used where expected is wanted?  Only says
+// no when it definitely can't
+fn fits(actual: &Type, expected: &Type) -> bool {
+  match (actual, expected) {
+    (&Type::Any, _) | (_, &Type::Any) => true,
+    (&Type::Elem(_, _), _) => true,
+    (&Type::Rest(_, _), &Type::List(_)) => true,
+    (&Type::List(_), &Type::List(_)) => true,
+    (a, e) if is_numeric(a) && is_numeric(e) => {
+      *a == *e || *a == Type::Number || *e == Type::Number
+    },
+    (a, e) => *a == *e,
+  }
+}
+
+fn union(a: &Type, b: &Type) -> Type {
+  if *a == *b {
+    a.clone()
+  } else if is_numeric(a) && is_numeric(b) {
+    Type::Number
+  } else {
+    match (a, b) {
+      (&Type::List(_), &Type::List(_)) => Type::List(None),
+      _ => Type::Any,
+    }
+  }
+}
+
+// The narrower of two requirements on the same thing, if they're compatible
+fn narrow(a: &Type, b: &Type) -> Option<Type> {
+  if !fits(a, b) || !fits(b, a) {
+    return None;
+  }
+  match (a, b) {
+    (&Type::Any, _) | (&Type::Number, _) | (&Type::List(None), _) => Some(b.clone()),
+    _ => Some(a.clone()),
+  }
+}
+
+// Replace a callee's symbolic types with what the caller actually passed
+fn substitute(t: &Type, args: &[Type]) -> Type {
+  match *t {
+    Type::Elem(0, k) => args.get(k).cloned().unwrap_or(Type::Any),
+    Type::Rest(0, n) => {
+      Type::List(Some(args.iter().skip(n).cloned().collect()))
+    },
+    Type::Elem(m, k) => Type::Elem(m - 1, k),
+    Type::Rest(m, n) => Type::Rest(m - 1, n),
+    Type::List(Some(ref items)) => {
+      Type::List(Some(items.iter().map(|i| substitute(i, args)).collect()))
+    },
+    ref other => other.clone(),
+  }
+}
+
+// Number of items each primitive takes (as a range), for the ones we know
+fn primitive_arity(id: &str) -> Option<(usize, usize)> {
+  let n = match id {
+    "<<" | "<<<" | "script" | "pi" | "e" => 0,
+    "int" | "float" | "string" | "symbol" | "list" | "unlist" | ">>" | "env" |
+    "!" | "strlen" | "car" | "cdr" | "catch" | "raise" | "~" | "fread" | "flines" |
+    "fexists" | "dir" | "bigint" | "sqrt" | "exp" | "ln" | "sin" | "cos" |
+    "tan" | "asin" | "acos" | "atan" | "floor" | "ceil" | "round" | "trunc" |
+    "abs" | "bnot" | "popcount" | "clz" | "ctz" | "trim" | "upper" | "lower" |
+    "reverse" | "char" | "codepoint" => 1,
+    _ if primitives_type::PRIMITIVES.contains(&id) => 1,
+    "pow" | "atan2" | "band" | "bor" | "bxor" | "shl" | "ashr" | "lshr" => 2,
+    "log" => return Some((1, 2)),
+    "min" | "max" => return Some((1, usize::MAX)),
+    "+" | "-" | "*" | "/" | "%" | "&" | "|" | "=" | "==" | ">" | "<" | "fwrite" |
+    "fappend" => 2,
+    _ if primitives_int::PRIMITIVES.contains(&id) => 2,
+    "split" | "join" | "find" | "contains" | "starts_with" | "ends_with" |
+    "repeat" | "re_match" | "re_find_all" | "re_split" => 2,
+    "?" | "substr" | "replace" | "re_replace" => 3,
+    "assert" | "assert_raises" => return Some((1, 2)),
+    "assert_eq" => return Some((2, 3)),
+    _ => return None,
+  };
+  Some((n, n))
+}
+
+impl<'a> Checker<'a> {
+  fn problem(&mut self, lnum: isize, msg: String) {
+    self.problems.push(Problem { lnum, msg });
+  }
+
+  fn add_min(&mut self, frame: &mut Signature, m: usize, n: usize) {
+    let entry = frame.min.entry(m).or_insert(0);
+    if n > *entry {
+      *entry = n;
+    }
+  }
+
+  fn add_constraint(&mut self, frame: &mut Signature, key: (usize, usize),
+                    t: &Type, lnum: isize) {
+    if *t == Type::Any {
+      return;
+    }
+    let narrowed = match frame.constraints.get(&key) {
+      None => Some(t.clone()),
+      Some(existing) => narrow(existing, t),
+    };
+    match narrowed {
+      Some(n) => {
+        frame.constraints.insert(key, n);
+      },
+      None => {
+        let existing = frame.constraints[&key].clone();
+        self.problem(lnum, format!("{} is used as both {} and {}",
+                                   Type::Elem(key.0, key.1), existing, t));
+      },
+    }
+  }
+
+  fn require(&mut self, actual: &Type, expected: &Type, what: &str,
+             frame: &mut Signature, lnum: isize) {
+    match *actual {
+      Type::Elem(m, k) => self.add_constraint(frame, (m, k), expected, lnum),
+      _ => {
+        if !fits(actual, expected) {
+          self.problem(lnum, format!("{} should be {} but is {}",
+                                     what, expected, actual));
+        }
+      },
+    }
+  }
+
+  // Like require, but any of several types will do (so there's nothing
+  // single to remember about a symbolic parameter)
+  fn require_one_of(&mut self, actual: &Type, expected: &[Type], what: &str,
+                    lnum: isize) {
+    if !expected.iter().any(|e| fits(actual, e)) {
+      let names: Vec<String> = expected.iter().map(|e| e.to_string()).collect();
+      self.problem(lnum, format!("{} should be {} but is {}",
+                                 what, names.join(" or "), actual));
+    }
+  }
+
+  fn register(&mut self, block: &'a Block, env: &mut Vec<HashMap<String, usize>>) ->
+    Vec<usize> {
+    let mut names = HashMap::new();
+    let mut indexes = Vec::new();
+    for e in &block.expressions {
+      if let Expression::Definition(ref def) = *e {
+        if !def.id.is_empty() && !names.contains_key(&def.id) {
+          names.insert(def.id.clone(), self.defs.len());
+          indexes.push(self.defs.len());
+          self.defs.push(DefInfo { def, env: Vec::new(), signature: None,
+                                   busy: false });
+        }
+      }
+    }
+    env.push(names);
+    for i in &indexes {
+      self.defs[*i].env = env.clone();
+    }
+    indexes
+  }
+
+  fn analyze_def(&mut self, index: usize) -> Signature {
+    if let Some(ref sig) = self.defs[index].signature {
+      return sig.clone();
+    }
+    if self.defs[index].busy {
+      // Recursive call; we'll know more once we've finished
+      return Signature::unknown();
+    }
+    self.defs[index].busy = true;
+    let def = self.defs[index].def;
+    let mut env = self.defs[index].env.clone();
+    let mut frame = Signature::unknown();
+    let last = self.analyze_block(&def.block, &mut env, &mut frame);
+    frame.returns = last;
+    self.defs[index].busy = false;
+    self.defs[index].signature = Some(frame.clone());
+    frame
+  }
+
+  // Anything returned with ~ gets added to frame.returns as we go; the result
+  // here is the type of the last expression (union of the two, in the end)
+  fn analyze_block(&mut self, block: &'a Block,
+                   env: &mut Vec<HashMap<String, usize>>,
+                   frame: &mut Signature) -> Type {
+    let indexes = self.register(block, env);
+    let mut returned: Option<Type> = None;
+    let mut last = Type::Atom;
+    for e in &block.expressions {
+      last = self.analyze(e, env, frame, &mut returned);
+    }
+    for i in indexes {
+      self.analyze_def(i);
+    }
+    env.pop();
+    match returned {
+      Some(r) => union(&r, &last),
+      None => last,
+    }
+  }
+
+  fn analyze(&mut self, exp: &'a Expression, env: &mut Vec<HashMap<String, usize>>,
+             frame: &mut Signature, returned: &mut Option<Type>) -> Type {
+    match *exp {
+      Expression::True | Expression::False => Type::Atom,
+      Expression::Integer(_) | Expression::BigInt(_) => Type::Int,
+      Expression::Float(_) => Type::Float,
+      Expression::String(_) => Type::String,
+      Expression::Symbol(_) => Type::Symbol,
+      Expression::List(ref list) => {
+        let mut items = Vec::new();
+        for i in &list.items {
+          items.push(self.analyze(i, env, frame, returned));
+        }
+        Type::List(Some(items))
+      },
+      Expression::Definition(ref def) => {
+        let registered = env.last()
+          .and_then(|names| names.get(&def.id).cloned())
+          .filter(|i| std::ptr::eq(self.defs[*i].def, def));
+        match registered {
+          Some(i) => {
+            self.analyze_def(i);
+          },
+          None => {
+            // Anonymous (or otherwise not bound where we'd look for it), so
+            // just check what's inside
+            let mut inner = Signature::unknown();
+            self.analyze_block(&def.block, env, &mut inner);
+          },
+        }
+        Type::Function
+      },
+      Expression::Call(ref call) => self.analyze_call(call, env, frame, returned),
+    }
+  }
+
+  fn resolve(&self, id: &str, env: &[HashMap<String, usize>]) -> Option<usize> {
+    for names in env.iter().rev() {
+      if let Some(i) = names.get(id) {
+        return Some(*i);
+      }
+    }
+    None
+  }
+
+  fn analyze_call(&mut self, call: &'a Call, env: &mut Vec<HashMap<String, usize>>,
+                  frame: &mut Signature, returned: &mut Option<Type>) -> Type {
+    let mut args = Vec::new();
+    for i in &call.param.items {
+      args.push(self.analyze(i, env, frame, returned));
+    }
+    let id = &*call.id;
+    if !id.is_empty() && id.chars().all(|c| c == '_') {
+      return Type::Rest(id.len() - 1, 0);
+    }
+    if let Some(index) = self.resolve(id, env) {
+      let sig = self.analyze_def(index);
+      return self.apply(id, &sig, &args, frame, call.lnum);
+    }
+    self.primitive(id, &args, frame, returned, call.lnum)
+  }
+
+  // Check a call to a definition against its signature, and work out what
+  // it returns given these arguments
+  fn apply(&mut self, id: &str, sig: &Signature, args: &[Type],
+           frame: &mut Signature, lnum: isize) -> Type {
+    for (&m, &n) in &sig.min {
+      if m == 0 {
+        if args.len() < n {
+          self.problem(lnum, format!("{} needs at least {} argument(s) but gets {}",
+                                     id, n, args.len()));
+        }
+      } else {
+        self.add_min(frame, m - 1, n);
+      }
+    }
+    for (&(m, k), t) in &sig.constraints {
+      if m == 0 {
+        if k < args.len() {
+          self.require(&args[k], t, &format!("argument {} of {}", k, id),
+                       frame, lnum);
+        }
+      } else {
+        self.add_constraint(frame, (m - 1, k), t, lnum);
+      }
+    }
+    substitute(&sig.returns, args)
+  }
+
+  fn numeric(&mut self, id: &str, args: &[Type], frame: &mut Signature,
+             lnum: isize) -> Type {
+    for (n, a) in args.iter().enumerate() {
+      self.require(a, &Type::Number, &format!("argument {} of {}", n, id),
+                   frame, lnum);
+    }
+    if args.contains(&Type::Float) {
+      Type::Float
+    } else if args.iter().all(|a| *a == Type::Int) {
+      Type::Int
+    } else {
+      Type::Number
+    }
+  }
+
+  fn primitive(&mut self, id: &str, args: &[Type], frame: &mut Signature,
+               returned: &mut Option<Type>, lnum: isize) -> Type {
+    if id == "," {
+      if args.len() < 2 {
+        self.problem(lnum, format!(", needs 2 arguments but gets {}", args.len()));
+      } else {
+        self.require(&args[0], &Type::Function, "argument 0 of ,", frame, lnum);
+      }
+      return Type::Any;
+    }
+    if let Some((min, max)) = primitive_arity(id) {
+      if args.len() < min || args.len() > max {
+        let expected = if min == max {
+          min.to_string()
+        } else if max == usize::MAX {
+          format!("at least {}", min)
+        } else {
+          format!("{} or {}", min, max)
+        };
+        self.problem(lnum, format!("{} takes {} argument(s) but gets {}",
+                                   id, expected, args.len()));
+        return Type::Any;
+      }
+    }
+    let what = |n: usize| format!("argument {} of {}", n, id);
+    match id {
+      "int" | "bigint" | "float" => {
+        self.require_one_of(&args[0], &[Type::Int, Type::Float, Type::String], &what(0), lnum);
+        if id == "float" { Type::Float } else { Type::Int }
+      },
+      "string" => Type::String,
+      "symbol" => {
+        self.require_one_of(&args[0], &[Type::String, Type::Symbol], &what(0), lnum);
+        Type::Symbol
+      },
+      "type" => Type::Symbol,
+      _ if primitives_type::PRIMITIVES.contains(&id) => Type::Atom,
+      "list" => {
+        match args[0] {
+          Type::List(_) | Type::Rest(_, _) => args[0].clone(),
+          Type::String | Type::Any | Type::Elem(_, _) => Type::List(None),
+          ref t => Type::List(Some(vec![t.clone()])),
+        }
+      },
+      "unlist" => {
+        self.require(&args[0], &Type::List(None), &what(0), frame, lnum);
+        Type::String
+      },
+      ">>" => {
+        self.require(&args[0], &Type::String, &what(0), frame, lnum);
+        Type::Atom
+      },
+      "<<" | "env" | "script" => {
+        if id == "env" {
+          self.require(&args[0], &Type::String, &what(0), frame, lnum);
+        }
+        Type::Any
+      },
+      "<<<" => Type::String,
+      "+" => {
+        let (a, b) = (&args[0], &args[1]);
+        if is_symbolic(a) || is_symbolic(b) {
+          // Whichever one we know decides what the other has to be
+          let (known, unknown, n) = if is_symbolic(a) { (b, a, 0) } else { (a, b, 1) };
+          let family = match *known {
+            ref t if is_numeric(t) => Some(Type::Number),
+            Type::String => Some(Type::String),
+            Type::List(_) | Type::Rest(_, _) => Some(Type::List(None)),
+            Type::Atom | Type::Symbol | Type::Function => {
+              self.problem(lnum, format!("{} should be number, string or list but is {}",
+                                         what(1 - n), known));
+              None
+            },
+            _ => None,
+          };
+          if let Some(f) = family {
+            self.require(unknown, &f, &what(n), frame, lnum);
+            if f == Type::Number {
+              return Type::Number;
+            }
+            return f;
+          }
+          return Type::Any;
+        }
+        match (a, b) {
+          (x, y) if is_numeric(x) && is_numeric(y) => self.numeric(id, args, frame, lnum),
+          (&Type::String, &Type::String) => Type::String,
+          (&Type::List(Some(ref x)), &Type::List(Some(ref y))) => {
+            let mut items = x.clone();
+            items.extend(y.iter().cloned());
+            Type::List(Some(items))
+          },
+          (&Type::List(_), &Type::List(_)) | (&Type::List(_), &Type::Rest(_, _)) |
+          (&Type::Rest(_, _), &Type::List(_)) |
+          (&Type::Rest(_, _), &Type::Rest(_, _)) => Type::List(None),
+          (&Type::Any, _) | (_, &Type::Any) => Type::Any,
+          (x, y) => {
+            self.problem(lnum, format!("+ can't combine {} and {}", x, y));
+            Type::Any
+          },
+        }
+      },
+      "-" | "*" | "/" => self.numeric(id, args, frame, lnum),
+      _ if id == "%" || primitives_int::PRIMITIVES.contains(&id) ||
+        primitives_bits::PRIMITIVES.contains(&id) => {
+        for (n, a) in args.iter().enumerate() {
+          self.require(a, &Type::Int, &what(n), frame, lnum);
+        }
+        Type::Int
+      },
+      "!" | "&" | "|" => {
+        for (n, a) in args.iter().enumerate() {
+          self.require(a, &Type::Atom, &what(n), frame, lnum);
+        }
+        Type::Atom
+      },
+      "<" | ">" | "==" => {
+        self.numeric(id, args, frame, lnum);
+        Type::Atom
+      },
+      "abs" | "min" | "max" => self.numeric(id, args, frame, lnum),
+      "pow" => {
+        self.numeric(id, args, frame, lnum);
+        Type::Number
+      },
+      "floor" | "ceil" | "round" | "trunc" => {
+        self.numeric(id, args, frame, lnum);
+        Type::Int
+      },
+      "sqrt" | "exp" | "ln" | "log" | "sin" | "cos" | "tan" | "asin" | "acos" |
+      "atan" | "atan2" | "pi" | "e" => {
+        self.numeric(id, args, frame, lnum);
+        Type::Float
+      },
+      "=" => Type::Atom,
+      "?" => {
+        self.require(&args[0], &Type::Atom, &what(0), frame, lnum);
+        union(&args[1], &args[2])
+      },
+      "substr" => {
+        self.require(&args[0], &Type::String, &what(0), frame, lnum);
+        self.require(&args[1], &Type::Int, &what(1), frame, lnum);
+        self.require(&args[2], &Type::Int, &what(2), frame, lnum);
+        Type::String
+      },
+      "strlen" => {
+        self.require(&args[0], &Type::String, &what(0), frame, lnum);
+        Type::Int
+      },
+      "split" | "find" | "contains" | "starts_with" | "ends_with" | "replace" |
+      "trim" | "upper" | "lower" | "codepoint" | "re_match" | "re_find_all" |
+      "re_replace" | "re_split" => {
+        for (n, a) in args.iter().enumerate() {
+          self.require(a, &Type::String, &what(n), frame, lnum);
+        }
+        match id {
+          "split" | "re_match" | "re_find_all" | "re_split" => Type::List(None),
+          "find" | "codepoint" => Type::Int,
+          "contains" | "starts_with" | "ends_with" => Type::Atom,
+          _ => Type::String,
+        }
+      },
+      "join" => {
+        self.require(&args[0], &Type::List(None), &what(0), frame, lnum);
+        self.require(&args[1], &Type::String, &what(1), frame, lnum);
+        Type::String
+      },
+      "repeat" => {
+        self.require(&args[0], &Type::String, &what(0), frame, lnum);
+        self.require(&args[1], &Type::Int, &what(1), frame, lnum);
+        Type::String
+      },
+      "char" => {
+        self.require(&args[0], &Type::Int, &what(0), frame, lnum);
+        Type::String
+      },
+      "car" => {
+        match args[0] {
+          Type::List(Some(ref items)) => {
+            match items.first() {
+              Some(t) => t.clone(),
+              None => {
+                self.problem(lnum, "car of an empty list".to_string());
+                Type::Any
+              },
+            }
+          },
+          Type::Rest(m, n) => {
+            self.add_min(frame, m, n + 1);
+            Type::Elem(m, n)
+          },
+          ref t => {
+            self.require(t, &Type::List(None), &what(0), frame, lnum);
+            Type::Any
+          },
+        }
+      },
+      "cdr" => {
+        match args[0] {
+          Type::List(Some(ref items)) => {
+            Type::List(Some(items.iter().skip(1).cloned().collect()))
+          },
+          Type::Rest(m, n) => Type::Rest(m, n + 1),
+          ref t => {
+            self.require(t, &Type::List(None), &what(0), frame, lnum);
+            Type::List(None)
+          },
+        }
+      },
+      "catch" => Type::List(None),
+      "~" => {
+        *returned = Some(match returned.take() {
+          Some(r) => union(&r, &args[0]),
+          None => args[0].clone(),
+        });
+        Type::Any
+      },
+      "raise" => Type::Any,
+      "fread" | "fexists" | "flines" | "dir" | "fwrite" | "fappend" => {
+        for (n, a) in args.iter().enumerate() {
+          self.require(a, &Type::String, &what(n), frame, lnum);
+        }
+        match id {
+          "fread" => Type::String,
+          "fexists" => Type::Atom,
+          _ => Type::List(None),
+        }
+      },
+      "assert" => {
+        self.require(&args[0], &Type::Atom, &what(0), frame, lnum);
+        Type::Atom
+      },
+      _ => Type::Any,
+    }
+  }
+}
+
+pub fn check(source: &str) -> Result<(Vec<Problem>, Vec<Report>), ParseError> {
+  let tokens = tokenizer::tokenize(source)?;
+  let block = parser::parse(&tokens)?;
+  let mut checker = Checker { defs: Vec::new(), problems: Vec::new() };
+  let mut env = Vec::new();
+  let mut main = Signature::unknown();
+  checker.analyze_block(&block, &mut env, &mut main);
+
+  let mut reports = Vec::new();
+  for info in &checker.defs {
+    if let Some(ref sig) = info.signature {
+      reports.push(Report { name: info.def.id.clone(), lnum: info.def.lnum,
+                            signature: sig.clone() });
+    }
+  }
+  checker.problems.sort_by_key(|p| p.lnum);
+  Ok((checker.problems, reports))
+}
+
+// Returns the number of problems found (parse errors count as one); with
+// report, also prints what was inferred for each definition
+pub fn run(paths: &[String], report: bool) -> usize {
+  let mut files = Vec::new();
+  for path in paths {
+    testing::collect_files(Path::new(path), &mut files);
+  }
+  let mut count = 0;
+  for f in &files {
+    let mut source = String::new();
+    if let Err(e) = File::open(f).and_then(|mut x| x.read_to_string(&mut source)) {
+      println!("{}: unable to read file: {}", f, e);
+      count += 1;
+      continue;
+    }
+    if !source.ends_with('\n') {
+      source.push('\n');
+    }
+    match check(&source) {
+      Ok((problems, reports)) => {
+        for p in &problems {
+          println!("{}:{}: error: {}", f, p.lnum, p.msg);
+        }
+        count += problems.len();
+        if report {
+          for r in &reports {
+            println!("{}:{}: {}: {}", f, r.lnum, r.name, r.signature);
+          }
+        }
+      },
+      Err(e) => {
+        println!("{}:{}: error: {}", f, e.lnum, e.msg);
+        count += 1;
+      },
+    }
+  }
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use primitives;
+
+  fn problems(source: &str) -> Vec<(isize, String)> {
+    match check(source) {
+      Ok((problems, _)) => problems.into_iter().map(|p| (p.lnum, p.msg)).collect(),
+      Err(e) => panic!("{}", e),
+    }
+  }
+
+  #[test]
+  fn bad_call() {
+    assert_eq!(problems("x:1;;\n+[1 \"a\"];\n"),
+               vec![(2, "+ can't combine int and string".to_string())]);
+    assert_eq!(problems("car[1 2];\n"),
+               vec![(1, "car takes 1 argument(s) but gets 2".to_string())]);
+  }
+
+  #[test]
+  fn good_calls() {
+    assert!(problems("add:+[car[_] 1];;\nadd[2];\n+[\"a\" \"b\"];\n").is_empty());
+  }
+
+  #[test]
+  fn signature() {
+    let (_, reports) = check("add:+[car[_] 1];;\n").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(reports[0].name, "add");
+    assert_eq!(reports[0].signature.to_string(),
+               "_ has at least 1 item(s), _[0] is number; returns number");
+  }
+
+  // The only problems in the test suite are the calls it makes wrong on
+  // purpose, to check they raise
+  #[test]
+  fn test_suite() {
+    let source = include_str!("../test.cry");
+    let lines: Vec<&str> = source.lines().collect();
+    for (lnum, msg) in problems(source) {
+      let line = lines[lnum as usize - 1];
+      assert!(line.contains("check_error[") || line.contains("assert_raises["),
+              "test.cry:{}: {}", lnum, msg);
+    }
+  }
+
+  #[test]
+  fn arity_for_every_primitive() {
+    let missing: Vec<&str> = primitives::names().into_iter()
+      .filter(|p| *p != "," && primitive_arity(p).is_none()).collect();
+    assert!(missing.is_empty(), "no arity for {:?}", missing);
+  }
+}
@@ -0,0 +1,328 @@
+// Just enough JSON for the language server: parsing messages in, and building
+// and printing replies
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  // Kept in order, so output is predictable
+  Object(Vec<(String, Json)>)
+}
+
+impl Json {
+  pub fn get(&self, key: &str) -> &Json {
+    match *self {
+      Json::Object(ref fields) => {
+        for (k, v) in fields {
+          if k == key {
+            return v;
+          }
+        }
+        &Json::Null
+      },
+      _ => &Json::Null,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match *self {
+      Json::String(ref s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_usize(&self) -> Option<usize> {
+    match *self {
+      Json::Number(n) if n >= 0.0 => Some(n as usize),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> &[Json] {
+    match *self {
+      Json::Array(ref items) => items,
+      _ => &[],
+    }
+  }
+}
+
+// Shorthand for building objects: object(vec![("a", Json::Null), ...])
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+  Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+pub fn string(s: &str) -> Json {
+  Json::String(s.to_string())
+}
+
+pub fn number(n: usize) -> Json {
+  Json::Number(n as f64)
+}
+
+fn escape(s: &str) -> String {
+  let mut rc = String::new();
+  for c in s.chars() {
+    match c {
+      '"' => rc += "\\\"",
+      '\\' => rc += "\\\\",
+      '\n' => rc += "\\n",
+      '\r' => rc += "\\r",
+      '\t' => rc += "\\t",
+      c if (c as u32) < 0x20 => rc += &format!("\\u{:04x}", c as u32),
+      c => rc.push(c),
+    }
+  }
+  rc
+}
+
+impl Display for Json {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    match *self {
+      Json::Null => write!(f, "null"),
+      Json::Bool(b) => write!(f, "{}", b),
+      Json::Number(n) => {
+        if n.fract() == 0.0 && n.abs() < 1e15 {
+          write!(f, "{}", n as i64)
+        } else {
+          write!(f, "{}", n)
+        }
+      },
+      Json::String(ref s) => write!(f, "\"{}\"", escape(s)),
+      Json::Array(ref items) => {
+        let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+        write!(f, "[{}]", items.join(","))
+      },
+      Json::Object(ref fields) => {
+        let fields: Vec<String> = fields.iter()
+          .map(|(k, v)| format!("\"{}\":{}", escape(k), v))
+          .collect();
+        write!(f, "{{{}}}", fields.join(","))
+      },
+    }
+  }
+}
+
+struct Parser {
+  chars: Vec<char>,
+  index: usize
+}
+
+impl Parser {
+  fn error(&self, msg: &str) -> String {
+    format!("{} at character {}", msg, self.index)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while self.index < self.chars.len() && self.chars[self.index].is_whitespace() {
+      self.index += 1;
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.index).cloned()
+  }
+
+  fn expect(&mut self, c: char) -> Result<(), String> {
+    if self.peek() == Some(c) {
+      self.index += 1;
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected '{}'", c)))
+    }
+  }
+
+  fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+    for c in word.chars() {
+      self.expect(c)?;
+    }
+    Ok(value)
+  }
+
+  fn hex4(&mut self) -> Result<u32, String> {
+    let mut n = 0;
+    for _ in 0..4 {
+      let digit = self.peek().and_then(|c| c.to_digit(16))
+        .ok_or_else(|| self.error("bad \\u escape"))?;
+      n = n * 16 + digit;
+      self.index += 1;
+    }
+    Ok(n)
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    self.expect('"')?;
+    let mut rc = String::new();
+    loop {
+      let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+      self.index += 1;
+      match c {
+        '"' => return Ok(rc),
+        '\\' => {
+          let e = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+          self.index += 1;
+          match e {
+            'n' => rc.push('\n'),
+            'r' => rc.push('\r'),
+            't' => rc.push('\t'),
+            'b' => rc.push('\u{8}'),
+            'f' => rc.push('\u{c}'),
+            'u' => {
+              let mut code = self.hex4()?;
+              // Surrogate pair
+              if (0xd800..0xdc00).contains(&code) && self.peek() == Some('\\') {
+                self.index += 1;
+                self.expect('u')?;
+                let low = self.hex4()?;
+                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+              }
+              rc.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+            },
+            other => rc.push(other),
+          }
+        },
+        c => rc.push(c),
+      }
+    }
+  }
+
+  fn number(&mut self) -> Result<Json, String> {
+    let start = self.index;
+    while let Some(c) = self.peek() {
+      if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+        self.index += 1;
+      } else {
+        break;
+      }
+    }
+    let text: String = self.chars[start..self.index].iter().collect();
+    text.parse::<f64>().map(Json::Number).map_err(|_| self.error("bad number"))
+  }
+
+  fn value(&mut self) -> Result<Json, String> {
+    self.skip_whitespace();
+    let rc = match self.peek() {
+      Some('n') => self.literal("null", Json::Null)?,
+      Some('t') => self.literal("true", Json::Bool(true))?,
+      Some('f') => self.literal("false", Json::Bool(false))?,
+      Some('"') => Json::String(self.string()?),
+      Some('[') => {
+        self.index += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+          self.index += 1;
+        } else {
+          loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+              Some(',') => self.index += 1,
+              Some(']') => {
+                self.index += 1;
+                break;
+              },
+              _ => return Err(self.error("expected ',' or ']'")),
+            }
+          }
+        }
+        Json::Array(items)
+      },
+      Some('{') => {
+        self.index += 1;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+          self.index += 1;
+        } else {
+          loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+              Some(',') => self.index += 1,
+              Some('}') => {
+                self.index += 1;
+                break;
+              },
+              _ => return Err(self.error("expected ',' or '}'")),
+            }
+          }
+        }
+        Json::Object(fields)
+      },
+      Some(c) if c == '-' || c.is_ascii_digit() => self.number()?,
+      _ => return Err(self.error("unexpected character")),
+    };
+    Ok(rc)
+  }
+}
+
+pub fn parse(source: &str) -> Result<Json, String> {
+  let mut parser = Parser { chars: source.chars().collect(), index: 0 };
+  let rc = parser.value()?;
+  parser.skip_whitespace();
+  if parser.index < parser.chars.len() {
+    return Err(parser.error("trailing characters"));
+  }
+  Ok(rc)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn escapes() {
+    let rc = parse(r#""a\"b\\c\/d\n\tA""#);
+    assert_eq!(rc, Ok(string("a\"b\\c/d\n\tA")));
+  }
+
+  #[test]
+  fn surrogate_pairs() {
+    assert_eq!(parse(r#""\ud83d\ude00""#), Ok(string("\u{1f600}")));
+    assert_eq!(parse(r#""\u00e9""#), Ok(string("é")));
+    // A lone surrogate isn't a character
+    assert_eq!(parse(r#""\ud83d""#), Ok(string("\u{fffd}")));
+  }
+
+  #[test]
+  fn escapes_round_trip() {
+    let s = string("quote \" slash \\ newline \n bell \u{7}");
+    assert_eq!(parse(&s.to_string()), Ok(s));
+  }
+
+  #[test]
+  fn trailing_characters() {
+    assert_eq!(parse("1 "), Ok(Json::Number(1.0)));
+    assert_eq!(parse("[1] x"), Err("trailing characters at character 4".to_string()));
+    assert!(parse("{} {}").is_err());
+  }
+
+  #[test]
+  fn nested_objects() {
+    let rc = parse(r#"{"a": {"b": [1, {"c": null}], "d": true}, "e": "f"}"#).unwrap();
+    assert_eq!(rc.get("a").get("b").as_array()[1].get("c"), &Json::Null);
+    assert_eq!(rc.get("a").get("d"), &Json::Bool(true));
+    assert_eq!(rc.get("e").as_str(), Some("f"));
+    assert_eq!(rc.get("missing"), &Json::Null);
+    assert_eq!(rc.to_string(), r#"{"a":{"b":[1,{"c":null}],"d":true},"e":"f"}"#);
+  }
+
+  #[test]
+  fn errors() {
+    assert!(parse(r#"{"a" 1}"#).is_err());
+    assert!(parse("[1 2]").is_err());
+    assert!(parse(r#""abc"#).is_err());
+    assert!(parse(r#""\u12""#).is_err());
+  }
+}
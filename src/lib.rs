@@ -1,9 +1,34 @@
 pub mod encoding;
+pub mod bigint;
+pub mod regex;
+pub mod symbol;
 pub mod encoding_impl;
 pub mod encoding_display;
 
 pub mod tokenizer;
 pub mod parser;
 pub mod evaluator;
+pub mod repl;
+pub mod dump;
+pub mod testing;
+pub mod golden;
+pub mod lint;
+pub mod infer;
+pub mod json;
+pub mod lsp;
+pub mod debugger;
+pub mod trace;
+pub mod profile;
+pub mod coverage;
 
 pub mod primitives;
+pub mod primitives_fs;
+pub mod primitives_assert;
+pub mod primitives_int;
+pub mod primitives_bigint;
+pub mod primitives_math;
+pub mod primitives_bits;
+pub mod primitives_string;
+pub mod primitives_regex;
+pub mod primitives_type;
+pub mod host;
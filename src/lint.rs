@@ -0,0 +1,290 @@
+// Static checks for the usual Curry pitfalls.  None of these stop a program
+// from running, but they usually mean it won't do what it looks like it does
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use parser;
+use primitives;
+use testing;
+use tokenizer;
+
+use encoding::Token;
+use encoding::TokenValue;
+use encoding::Block;
+use encoding::Expression;
+use encoding::List;
+use encoding::Call;
+use encoding::Definition;
+use encoding::ParseError;
+
+pub struct Warning {
+  pub lnum: isize,
+  pub msg: String
+}
+
+struct Linter<'a> {
+  lines: Vec<&'a str>,
+  // Every named definition and every call anywhere in the file; scoping is
+  // dynamic, so we can't do much better than that
+  defined: HashSet<String>,
+  called: HashSet<String>,
+  // Calls made from the main block itself (not from inside any definition)
+  main_calls: HashSet<String>,
+  warnings: Vec<Warning>
+}
+
+fn is_parameter(id: &str) -> bool {
+  !id.is_empty() && id.chars().all(|c| c == '_')
+}
+
+impl<'a> Linter<'a> {
+  fn warn(&mut self, lnum: isize, msg: String) {
+    self.warnings.push(Warning { lnum, msg });
+  }
+
+  fn collect_list(&mut self, list: &List, depth: usize) {
+    for i in &list.items {
+      self.collect(i, depth);
+    }
+  }
+
+  fn collect_block(&mut self, block: &Block, depth: usize) {
+    for e in &block.expressions {
+      self.collect(e, depth);
+    }
+  }
+
+  fn collect(&mut self, exp: &Expression, depth: usize) {
+    match *exp {
+      Expression::List(ref list) => self.collect_list(list, depth),
+      Expression::Call(ref call) => {
+        self.called.insert(call.id.clone());
+        if depth == 0 {
+          self.main_calls.insert(call.id.clone());
+        }
+        self.collect_list(&call.param, depth);
+      },
+      Expression::Definition(ref def) => {
+        if !def.id.is_empty() {
+          self.defined.insert(def.id.clone());
+        }
+        self.collect_block(&def.block, depth + 1);
+      },
+      _ => {},
+    }
+  }
+
+  // depth is the number of definitions we're inside of, and top is the name
+  // of the outermost one
+  fn check_call(&mut self, call: &Call, depth: usize, top: &str) {
+    if is_parameter(&call.id) {
+      let reach = call.id.len();
+      if depth == 0 && reach > 1 {
+        self.warn(call.lnum, format!("{} reaches past the main scope", call.id));
+      } else if depth > 0 && reach > depth + 1 && self.main_calls.contains(top) {
+        self.warn(call.lnum,
+                  format!("{} reaches past the main scope when {} is called from the main block",
+                          call.id, top));
+      }
+    } else if !self.defined.contains(&call.id) &&
+      !primitives::is_primitive(&call.id) {
+      self.warn(call.lnum,
+                format!("call to {}, which is neither defined nor a primitive",
+                        call.id));
+    }
+  }
+
+  fn check_definition(&mut self, def: &Definition) {
+    if def.id.is_empty() {
+      return;
+    }
+    if primitives::is_primitive(&def.id) {
+      self.warn(def.lnum, format!("definition of {} hides the primitive", def.id));
+    }
+    if !self.called.contains(&def.id) && !testing::is_marked(&self.lines, def.lnum) {
+      self.warn(def.lnum, format!("{} is defined but never used", def.id));
+    }
+  }
+
+  fn check_list(&mut self, list: &List, depth: usize, top: &str) {
+    for i in &list.items {
+      self.check(i, depth, top);
+    }
+  }
+
+  fn check_block(&mut self, block: &Block, depth: usize, top: &str) {
+    let mut seen: HashMap<&str, isize> = HashMap::new();
+    for e in &block.expressions {
+      if let Expression::Definition(ref def) = *e {
+        if def.id.is_empty() {
+          continue;
+        }
+        match seen.get(&*def.id) {
+          Some(lnum) => {
+            self.warn(def.lnum,
+                      format!("{} is already defined in this block (line {}), so this raises a redefinition error",
+                              def.id, lnum));
+          },
+          None => {
+            seen.insert(&def.id, def.lnum);
+          },
+        }
+      }
+    }
+    for e in &block.expressions {
+      self.check(e, depth, top);
+    }
+  }
+
+  fn check(&mut self, exp: &Expression, depth: usize, top: &str) {
+    match *exp {
+      Expression::List(ref list) => self.check_list(list, depth, top),
+      Expression::Call(ref call) => {
+        self.check_call(call, depth, top);
+        self.check_list(&call.param, depth, top);
+      },
+      Expression::Definition(ref def) => {
+        self.check_definition(def);
+        let top = if depth == 0 { &def.id } else { top };
+        self.check_block(&def.block, depth + 1, top);
+      },
+      _ => {},
+    }
+  }
+
+  // This one's lexical: inside a list, `foo [1]` is a call to foo with [1],
+  // not two items, whatever the whitespace looks like
+  fn check_tokens(&mut self, tokens: &[Token]) {
+    let mut depth = 0;
+    for i in 0..tokens.len() {
+      match tokens[i].value {
+        TokenValue::OpenBracket => depth += 1,
+        TokenValue::CloseBracket => depth -= 1,
+        TokenValue::ID(ref id) if depth > 0 && i + 1 < tokens.len() => {
+          let next = &tokens[i + 1];
+          if let TokenValue::OpenBracket = next.value {
+            if next.lnum != tokens[i].lnum ||
+              next.col > tokens[i].col + id.chars().count() {
+              self.warn(tokens[i].lnum,
+                        format!("{} [...] in a list passes the list after it to {} as its argument; use {}[] [...] for separate items",
+                                id, id, id));
+            }
+          }
+        },
+        _ => {},
+      }
+    }
+  }
+}
+
+pub fn lint(source: &str) -> Result<Vec<Warning>, ParseError> {
+  let tokens = tokenizer::tokenize(source)?;
+  let block = parser::parse(&tokens)?;
+  let mut linter = Linter {
+    lines: source.lines().collect(),
+    defined: HashSet::new(),
+    called: HashSet::new(),
+    main_calls: HashSet::new(),
+    warnings: Vec::new()
+  };
+  linter.collect_block(&block, 0);
+  linter.check_block(&block, 0, "");
+  linter.check_tokens(&tokens);
+  linter.warnings.sort_by_key(|w| w.lnum);
+  Ok(linter.warnings)
+}
+
+// Returns the number of warnings (parse errors count as one)
+pub fn run(paths: &[String]) -> usize {
+  let mut files = Vec::new();
+  for path in paths {
+    testing::collect_files(Path::new(path), &mut files);
+  }
+  let mut count = 0;
+  for f in &files {
+    let mut source = String::new();
+    if let Err(e) = File::open(f).and_then(|mut x| x.read_to_string(&mut source)) {
+      println!("{}: unable to read file: {}", f, e);
+      count += 1;
+      continue;
+    }
+    if !source.ends_with('\n') {
+      source.push('\n');
+    }
+    match lint(&source) {
+      Ok(warnings) => {
+        for w in &warnings {
+          println!("{}:{}: warning: {}", f, w.lnum, w.msg);
+        }
+        count += warnings.len();
+      },
+      Err(e) => {
+        println!("{}:{}: error: {}", f, e.lnum, e.msg);
+        count += 1;
+      },
+    }
+  }
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn warnings(source: &str) -> Vec<(isize, String)> {
+    match lint(source) {
+      Ok(warnings) => warnings.into_iter().map(|w| (w.lnum, w.msg)).collect(),
+      Err(e) => panic!("{}", e),
+    }
+  }
+
+  fn warning(lnum: isize, msg: &str) -> Vec<(isize, String)> {
+    vec![(lnum, msg.to_string())]
+  }
+
+  #[test]
+  fn call_then_list_in_list() {
+    assert_eq!(warnings("f:car[_];;\n[f [1]];\n"),
+               warning(2, "f [...] in a list passes the list after it to f as its argument; use f[] [...] for separate items"));
+    assert!(warnings("f:car[_];;\n[f[] [1]];\n").is_empty());
+  }
+
+  #[test]
+  fn parameter_depth() {
+    assert_eq!(warnings("car[__];\n"), warning(1, "__ reaches past the main scope"));
+    assert_eq!(warnings("f:car[___];;\nf[];\n"),
+               warning(1, "___ reaches past the main scope when f is called from the main block"));
+    assert!(warnings("f:car[__];;\ng:f[];;\ng[];\n").is_empty());
+  }
+
+  #[test]
+  fn hiding_a_primitive() {
+    assert_eq!(warnings("car:1;;\ncar[];\n"), warning(1, "definition of car hides the primitive"));
+    assert!(warnings("kar:1;;\nkar[];\n").is_empty());
+  }
+
+  #[test]
+  fn redefinition() {
+    assert_eq!(warnings("f:1;;\nf:2;;\nf[];\n"),
+               warning(2, "f is already defined in this block (line 1), so this raises a redefinition error"));
+    // Defining it again inside another definition just hides it there
+    assert!(warnings("f:1;;\ng:f:2;;f[];;\ng[];\n").is_empty());
+  }
+
+  #[test]
+  fn unused_definition() {
+    assert_eq!(warnings("f:1;;\n"), warning(1, "f is defined but never used"));
+    assert!(warnings("f:1;;\nf[];\n").is_empty());
+  }
+
+  #[test]
+  fn undefined_call() {
+    assert_eq!(warnings("nope[];\n"),
+               warning(1, "call to nope, which is neither defined nor a primitive"));
+    assert!(warnings("car[[1]];\n").is_empty());
+  }
+}
@@ -0,0 +1,633 @@
+// Language server (the Language Server Protocol over stdin/stdout), for
+// editor support: parse errors and lint/check problems as diagnostics,
+// go-to-definition, hover, document symbols and completion.
+//
+// Documents are synced in full on every change; they're small.  Definitions
+// are resolved lexically (innermost enclosing block first), which is what the
+// dynamic scoping does too for the usual case of calling something from where
+// it's visible
+
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+
+use infer;
+use json;
+use json::Json;
+use lint;
+use parser;
+use primitives;
+use tokenizer;
+
+use encoding::Token;
+use encoding::TokenValue;
+use encoding::Block;
+use encoding::Expression;
+use encoding::Definition;
+
+const ERROR: usize = 1;
+const WARNING: usize = 2;
+
+// LSP symbol and completion item kinds
+const SYMBOL_FUNCTION: usize = 12;
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_KEYWORD: usize = 14;
+
+struct Document {
+  lines: Vec<Vec<char>>,
+  tokens: Vec<Token>,
+  block: Option<Block>,
+  // Token index for each (lnum, col), so definitions can find where they are
+  token_at: HashMap<(isize, usize), usize>
+}
+
+// Where a definition is: start of its name (or colon, if it's anonymous),
+// end of its name and the end of the closing semicolon, as (line, column)
+// with both 0-based
+struct Extent {
+  start: (usize, usize),
+  name_end: (usize, usize),
+  end: (usize, usize)
+}
+
+fn is_colon(tokens: &[Token], index: usize) -> bool {
+  matches!(tokens.get(index).map(|t| &t.value), Some(&TokenValue::Colon))
+}
+
+fn is_semicolon(tokens: &[Token], index: usize) -> bool {
+  matches!(tokens.get(index).map(|t| &t.value), Some(&TokenValue::Semicolon))
+}
+
+// These follow the parser, but only to find where things end: each returns
+// the index of the token after the thing it skips (for definitions, that's
+// the semicolon that closes the block, which also ends the expression)
+fn skip_expression(tokens: &[Token], index: usize) -> Option<usize> {
+  match tokens.get(index)?.value {
+    TokenValue::ID(_) if is_colon(tokens, index + 1) => skip_block(tokens, index + 2),
+    TokenValue::ID(_) => {
+      match tokens.get(index + 1)?.value {
+        TokenValue::OpenBracket => skip_list(tokens, index + 1),
+        _ => Some(index + 1),
+      }
+    },
+    TokenValue::Colon => skip_block(tokens, index + 1),
+    TokenValue::OpenBracket => skip_list(tokens, index),
+    TokenValue::True | TokenValue::False | TokenValue::Integer(_) |
+    TokenValue::BigInt(_) | TokenValue::Float(_) | TokenValue::String(_) |
+    TokenValue::Symbol(_) => Some(index + 1),
+    _ => None,
+  }
+}
+
+fn skip_list(tokens: &[Token], start: usize) -> Option<usize> {
+  let mut index = start + 1;
+  loop {
+    if let TokenValue::CloseBracket = tokens.get(index)?.value {
+      return Some(index + 1);
+    }
+    index = skip_expression(tokens, index)?;
+  }
+}
+
+fn skip_block(tokens: &[Token], start: usize) -> Option<usize> {
+  let mut index = start;
+  loop {
+    match skip_expression(tokens, index) {
+      Some(next) if is_semicolon(tokens, next) => index = next + 1,
+      Some(_) => return None,
+      None => return if is_semicolon(tokens, index) { Some(index) } else { None },
+    }
+  }
+}
+
+fn position(lnum: isize, col: usize) -> (usize, usize) {
+  ((lnum - 1).max(0) as usize, col)
+}
+
+impl Document {
+  fn new(text: &str) -> Document {
+    let mut source = text.to_string();
+    if !source.ends_with('\n') {
+      source.push('\n');
+    }
+    let lines = source.lines().map(|l| l.chars().collect()).collect();
+    let tokens = tokenizer::tokenize(&source).unwrap_or_default();
+    let block = parser::parse(&tokens).ok();
+    let mut token_at = HashMap::new();
+    for (i, t) in tokens.iter().enumerate() {
+      token_at.insert((t.lnum, t.col), i);
+    }
+    Document { lines, tokens, block, token_at }
+  }
+
+  fn extent(&self, def: &Definition) -> Option<Extent> {
+    let index = *self.token_at.get(&(def.lnum, def.col))?;
+    let start = position(def.lnum, def.col);
+    let name_end = (start.0, start.1 + def.id.chars().count().max(1));
+    let body = if def.id.is_empty() { index + 1 } else { index + 2 };
+    let close = &self.tokens[skip_block(&self.tokens, body)?];
+    let end = position(close.lnum, close.col + 1);
+    Some(Extent { start, name_end, end })
+  }
+
+  // LSP positions count UTF-16 code units; ours count chars
+  fn lsp_position(&self, (line, col): (usize, usize)) -> Json {
+    let units = match self.lines.get(line) {
+      Some(chars) => chars.iter().take(col).map(|c| c.len_utf16()).sum(),
+      None => col,
+    };
+    json::object(vec![("line", json::number(line)), ("character", json::number(units))])
+  }
+
+  fn position_from(&self, pos: &Json) -> (usize, usize) {
+    let line = pos.get("line").as_usize().unwrap_or(0);
+    let units = pos.get("character").as_usize().unwrap_or(0);
+    let mut col = 0;
+    let mut seen = 0;
+    if let Some(chars) = self.lines.get(line) {
+      for c in chars {
+        if seen >= units {
+          break;
+        }
+        seen += c.len_utf16();
+        col += 1;
+      }
+    }
+    (line, col)
+  }
+
+  fn range(&self, start: (usize, usize), end: (usize, usize)) -> Json {
+    json::object(vec![("start", self.lsp_position(start)), ("end", self.lsp_position(end))])
+  }
+
+  fn text(&self, start: (usize, usize), end: (usize, usize)) -> String {
+    let mut rc = Vec::new();
+    for line in start.0..(end.0 + 1).min(self.lines.len()) {
+      let chars = &self.lines[line];
+      let from = if line == start.0 { start.1.min(chars.len()) } else { 0 };
+      let to = if line == end.0 { end.1.min(chars.len()) } else { chars.len() };
+      rc.push(chars[from..to.max(from)].iter().collect::<String>());
+    }
+    rc.join("\n")
+  }
+
+  // The identifier under the cursor, as the (lnum, col) it starts at
+  fn id_at(&self, (line, col): (usize, usize)) -> Option<(isize, usize, String)> {
+    for t in &self.tokens {
+      if let TokenValue::ID(ref id) = t.value {
+        if position(t.lnum, t.col).0 == line && t.col <= col &&
+          col <= t.col + id.chars().count() {
+          return Some((t.lnum, t.col, id.clone()));
+        }
+      }
+    }
+    None
+  }
+
+  fn diagnostics(&self, source: &str) -> Vec<Json> {
+    let mut rc = Vec::new();
+    let mut source = source.to_string();
+    if !source.ends_with('\n') {
+      source.push('\n');
+    }
+    let whole_line = |lnum: isize| {
+      // -1 means the error is at the end of the file
+      let line = if lnum < 1 { self.lines.len().saturating_sub(1) } else { lnum as usize - 1 };
+      let len = self.lines.get(line).map(|l| l.len()).unwrap_or(0);
+      self.range((line, 0), (line, len))
+    };
+    let diagnostic = |lnum: isize, severity: usize, source: &str, msg: &str| {
+      json::object(vec![("range", whole_line(lnum)),
+                        ("severity", json::number(severity)),
+                        ("source", json::string(source)),
+                        ("message", json::string(msg))])
+    };
+    match lint::lint(&source) {
+      Ok(warnings) => {
+        for w in warnings {
+          rc.push(diagnostic(w.lnum, WARNING, "curry lint", &w.msg));
+        }
+      },
+      Err(e) => {
+        rc.push(diagnostic(e.lnum, ERROR, "curry", &e.msg));
+        return rc;
+      },
+    }
+    if let Ok((problems, _)) = infer::check(&source) {
+      for p in problems {
+        rc.push(diagnostic(p.lnum, ERROR, "curry check", &p.msg));
+      }
+    }
+    rc
+  }
+}
+
+// Finds the definition a name at (lnum, col) refers to, looking outward from
+// the innermost block around it
+fn resolve<'a>(block: &'a Block, target: (isize, usize),
+               env: &mut Vec<Vec<&'a Definition>>) -> Option<&'a Definition> {
+  env.push(block.expressions.iter().filter_map(|e| match *e {
+    Expression::Definition(ref def) if !def.id.is_empty() => Some(def),
+    _ => None,
+  }).collect());
+  let mut rc = None;
+  for e in &block.expressions {
+    rc = resolve_expression(e, target, env);
+    if rc.is_some() {
+      break;
+    }
+  }
+  env.pop();
+  rc
+}
+
+fn resolve_expression<'a>(exp: &'a Expression, target: (isize, usize),
+                          env: &mut Vec<Vec<&'a Definition>>) -> Option<&'a Definition> {
+  match *exp {
+    Expression::Definition(ref def) => {
+      if !def.id.is_empty() && (def.lnum, def.col) == target {
+        return Some(def);
+      }
+      resolve(&def.block, target, env)
+    },
+    Expression::Call(ref call) => {
+      if (call.lnum, call.col) == target {
+        for defs in env.iter().rev() {
+          if let Some(def) = defs.iter().find(|d| d.id == call.id) {
+            return Some(def);
+          }
+        }
+        return None;
+      }
+      call.param.items.iter().filter_map(|i| resolve_expression(i, target, env)).next()
+    },
+    Expression::List(ref list) => {
+      list.items.iter().filter_map(|i| resolve_expression(i, target, env)).next()
+    },
+    _ => None,
+  }
+}
+
+fn contains(extent: &Extent, pos: (usize, usize)) -> bool {
+  extent.start <= pos && pos <= extent.end
+}
+
+impl Document {
+  fn symbols(&self, block: &Block) -> Vec<Json> {
+    let mut rc = Vec::new();
+    for e in &block.expressions {
+      if let Expression::Definition(ref def) = *e {
+        let children = self.symbols(&def.block);
+        match self.extent(def) {
+          Some(ref x) if !def.id.is_empty() => {
+            rc.push(json::object(vec![
+              ("name", json::string(&def.id)),
+              ("kind", json::number(SYMBOL_FUNCTION)),
+              ("range", self.range(x.start, x.end)),
+              ("selectionRange", self.range(x.start, x.name_end)),
+              ("children", Json::Array(children))]));
+          },
+          // Anonymous functions don't get a symbol, but what's in them does
+          _ => rc.extend(children),
+        }
+      }
+    }
+    rc
+  }
+
+  // Definitions visible from pos: those in every block it's inside of
+  fn visible(&self, block: &Block, pos: (usize, usize), rc: &mut Vec<String>) {
+    for e in &block.expressions {
+      if let Expression::Definition(ref def) = *e {
+        if !def.id.is_empty() && !rc.contains(&def.id) {
+          rc.push(def.id.clone());
+        }
+        if let Some(x) = self.extent(def) {
+          if contains(&x, pos) {
+            self.visible(&def.block, pos, rc);
+          }
+        }
+      }
+    }
+  }
+
+  fn definition(&self, pos: (usize, usize)) -> Option<&Definition> {
+    let (lnum, col, _) = self.id_at(pos)?;
+    resolve(self.block.as_ref()?, (lnum, col), &mut Vec::new())
+  }
+
+  fn location(&self, uri: &str, pos: (usize, usize)) -> Json {
+    match self.definition(pos).and_then(|def| self.extent(def)) {
+      Some(x) => json::object(vec![("uri", json::string(uri)),
+                                   ("range", self.range(x.start, x.name_end))]),
+      None => Json::Null,
+    }
+  }
+
+  fn hover(&self, pos: (usize, usize)) -> Json {
+    let contents = match self.definition(pos).and_then(|def| self.extent(def)) {
+      Some(x) => format!("```\n{}\n```", self.text(x.start, x.end)),
+      None => {
+        match self.id_at(pos) {
+          Some((_, _, ref id)) if primitives::is_primitive(id) => {
+            format!("`{}` (primitive)", id)
+          },
+          _ => return Json::Null,
+        }
+      },
+    };
+    json::object(vec![("contents", json::object(vec![
+      ("kind", json::string("markdown")), ("value", Json::String(contents))]))])
+  }
+
+  fn completion(&self, pos: (usize, usize)) -> Json {
+    let mut defs = Vec::new();
+    if let Some(ref block) = self.block {
+      self.visible(block, pos, &mut defs);
+    }
+    let mut items = Vec::new();
+    for d in &defs {
+      items.push(json::object(vec![("label", json::string(d)),
+                                   ("kind", json::number(COMPLETION_FUNCTION)),
+                                   ("detail", json::string("definition"))]));
+    }
+    for p in primitives::names() {
+      if !defs.iter().any(|d| d == p) {
+        items.push(json::object(vec![("label", json::string(p)),
+                                     ("kind", json::number(COMPLETION_KEYWORD)),
+                                     ("detail", json::string("primitive"))]));
+      }
+    }
+    Json::Array(items)
+  }
+}
+
+fn read_message(input: &mut dyn BufRead) -> io::Result<Option<String>> {
+  let mut length = None;
+  loop {
+    let mut header = String::new();
+    if input.read_line(&mut header)? == 0 {
+      return Ok(None);
+    }
+    let header = header.trim();
+    if header.is_empty() {
+      break;
+    }
+    let mut parts = header.splitn(2, ':');
+    if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+      if name.trim().eq_ignore_ascii_case("Content-Length") {
+        length = value.trim().parse::<usize>().ok();
+      }
+    }
+  }
+  let length = match length {
+    Some(n) => n,
+    None => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "missing Content-Length header")),
+  };
+  let mut body = vec![0; length];
+  input.read_exact(&mut body)?;
+  Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+fn send(output: &mut dyn Write, message: &Json) -> io::Result<()> {
+  let body = message.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  output.flush()
+}
+
+fn response(id: &Json, result: Json) -> Json {
+  json::object(vec![("jsonrpc", json::string("2.0")), ("id", id.clone()),
+                    ("result", result)])
+}
+
+fn error_response(id: &Json, code: i64, msg: &str) -> Json {
+  json::object(vec![("jsonrpc", json::string("2.0")), ("id", id.clone()),
+                    ("error", json::object(vec![("code", Json::Number(code as f64)),
+                                                ("message", json::string(msg))]))])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+  json::object(vec![("jsonrpc", json::string("2.0")), ("method", json::string(method)),
+                    ("params", params)])
+}
+
+fn capabilities() -> Json {
+  json::object(vec![("capabilities", json::object(vec![
+    // Full document sync
+    ("textDocumentSync", json::number(1)),
+    ("definitionProvider", Json::Bool(true)),
+    ("hoverProvider", Json::Bool(true)),
+    ("documentSymbolProvider", Json::Bool(true)),
+    ("completionProvider", json::object(vec![]))])),
+    ("serverInfo", json::object(vec![("name", json::string("curry"))]))])
+}
+
+struct Server {
+  documents: HashMap<String, (String, Document)>,
+  shutdown: bool
+}
+
+impl Server {
+  fn publish(&self, output: &mut dyn Write, uri: &str) -> io::Result<()> {
+    let diagnostics = match self.documents.get(uri) {
+      Some((text, doc)) => doc.diagnostics(text),
+      None => Vec::new(),
+    };
+    send(output, &notification("textDocument/publishDiagnostics", json::object(vec![
+      ("uri", json::string(uri)), ("diagnostics", Json::Array(diagnostics))])))
+  }
+
+  fn update(&mut self, uri: &str, text: &str) {
+    self.documents.insert(uri.to_string(), (text.to_string(), Document::new(text)));
+  }
+
+  // The result for a request (notifications get Null, which never gets sent),
+  // or an error code and message
+  fn handle(&mut self, method: &str, params: &Json, output: &mut dyn Write) ->
+    io::Result<Result<Json, (i64, String)>> {
+    let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+    let rc = match method {
+      "initialize" => capabilities(),
+      "shutdown" => {
+        self.shutdown = true;
+        Json::Null
+      },
+      "textDocument/didOpen" => {
+        let text = params.get("textDocument").get("text").as_str().unwrap_or("");
+        self.update(&uri, text);
+        self.publish(output, &uri)?;
+        Json::Null
+      },
+      "textDocument/didChange" => {
+        if let Some(change) = params.get("contentChanges").as_array().last() {
+          let text = change.get("text").as_str().unwrap_or("").to_string();
+          self.update(&uri, &text);
+          self.publish(output, &uri)?;
+        }
+        Json::Null
+      },
+      "textDocument/didClose" => {
+        self.documents.remove(&uri);
+        self.publish(output, &uri)?;
+        Json::Null
+      },
+      "textDocument/definition" | "textDocument/hover" |
+      "textDocument/documentSymbol" | "textDocument/completion" => {
+        let doc = match self.documents.get(&uri) {
+          Some((_, doc)) => doc,
+          None => return Ok(Err((-32602, format!("unknown document: {}", uri)))),
+        };
+        let pos = doc.position_from(params.get("position"));
+        match method {
+          "textDocument/definition" => doc.location(&uri, pos),
+          "textDocument/hover" => doc.hover(pos),
+          "textDocument/documentSymbol" => {
+            match doc.block {
+              Some(ref block) => Json::Array(doc.symbols(block)),
+              None => Json::Null,
+            }
+          },
+          _ => doc.completion(pos),
+        }
+      },
+      _ => return Ok(Err((-32601, format!("method not found: {}", method)))),
+    };
+    Ok(Ok(rc))
+  }
+}
+
+// Serve until the client says exit; returns the exit status
+pub fn run() -> i32 {
+  let stdin = io::stdin();
+  let mut input = stdin.lock();
+  let stdout = io::stdout();
+  let mut output = stdout.lock();
+  let mut server = Server { documents: HashMap::new(), shutdown: false };
+  loop {
+    let body = match read_message(&mut input) {
+      Ok(Some(body)) => body,
+      Ok(None) => return 1,
+      Err(e) => {
+        eprintln!("curry lsp: {}", e);
+        return 1;
+      },
+    };
+    let message = match json::parse(&body) {
+      Ok(message) => message,
+      Err(e) => {
+        if send(&mut output, &error_response(&Json::Null, -32700, &e)).is_err() {
+          return 1;
+        }
+        continue;
+      },
+    };
+    let method = message.get("method").as_str().unwrap_or("").to_string();
+    if method == "exit" {
+      return if server.shutdown { 0 } else { 1 };
+    }
+    let id = message.get("id");
+    let result = match server.handle(&method, message.get("params"), &mut output) {
+      Ok(result) => result,
+      Err(_) => return 1,
+    };
+    // Notifications (no id) don't get a reply, even if we didn't understand
+    // them
+    if *id == Json::Null {
+      continue;
+    }
+    let reply = match result {
+      Ok(result) => response(id, result),
+      Err((code, msg)) => error_response(id, code, &msg),
+    };
+    if send(&mut output, &reply).is_err() {
+      return 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const URI: &str = "file:///test.cry";
+  const SOURCE: &str = "double:+[car[_] car[_]];;\nmain:double[[1]];;\n";
+
+  fn server() -> Server {
+    let mut server = Server { documents: HashMap::new(), shutdown: false };
+    let params = json::object(vec![("textDocument", json::object(vec![
+      ("uri", json::string(URI)), ("text", json::string(SOURCE))]))]);
+    let mut output = Vec::new();
+    let rc = server.handle("textDocument/didOpen", &params, &mut output).unwrap();
+    assert_eq!(rc, Ok(Json::Null));
+    server
+  }
+
+  fn request(method: &str, line: usize, character: usize) -> Json {
+    let params = json::object(vec![
+      ("textDocument", json::object(vec![("uri", json::string(URI))])),
+      ("position", json::object(vec![("line", json::number(line)),
+                                     ("character", json::number(character))]))]);
+    let mut output = Vec::new();
+    server().handle(method, &params, &mut output).unwrap().unwrap()
+  }
+
+  fn range(start: (usize, usize), end: (usize, usize)) -> Json {
+    let position = |(line, character): (usize, usize)| {
+      json::object(vec![("line", json::number(line)), ("character", json::number(character))])
+    };
+    json::object(vec![("start", position(start)), ("end", position(end))])
+  }
+
+  #[test]
+  fn definition() {
+    let rc = request("textDocument/definition", 1, 7);
+    assert_eq!(rc, json::object(vec![("uri", json::string(URI)),
+                                     ("range", range((0, 0), (0, 6)))]));
+    // Primitives aren't defined anywhere
+    assert_eq!(request("textDocument/definition", 0, 8), Json::Null);
+  }
+
+  #[test]
+  fn hover() {
+    let rc = request("textDocument/hover", 1, 7);
+    assert_eq!(rc.get("contents").get("value").as_str(),
+               Some("```\ndouble:+[car[_] car[_]];;\n```"));
+    let rc = request("textDocument/hover", 0, 7);
+    assert_eq!(rc.get("contents").get("value").as_str(), Some("`+` (primitive)"));
+    assert_eq!(request("textDocument/hover", 1, 17), Json::Null);
+  }
+
+  #[test]
+  fn completion() {
+    let rc = request("textDocument/completion", 1, 7);
+    let labels: Vec<&str> = rc.as_array().iter().filter_map(|i| i.get("label").as_str()).collect();
+    assert_eq!(&labels[..2], &["double", "main"]);
+    assert!(labels.contains(&"car"));
+    assert_eq!(rc.as_array()[0].get("detail").as_str(), Some("definition"));
+  }
+
+  #[test]
+  fn diagnostics_are_published() {
+    let mut server = Server { documents: HashMap::new(), shutdown: false };
+    let params = json::object(vec![("textDocument", json::object(vec![
+      ("uri", json::string(URI)), ("text", json::string("+[1 \"a\"];\n"))]))]);
+    let mut output = Vec::new();
+    server.handle("textDocument/didOpen", &params, &mut output).unwrap().unwrap();
+    let sent = String::from_utf8(output).unwrap();
+    assert!(sent.starts_with("Content-Length: "));
+    assert!(sent.contains("publishDiagnostics"));
+    assert!(sent.contains("+ can't combine int and string"));
+  }
+
+  #[test]
+  fn unknown_method_and_document() {
+    let mut output = Vec::new();
+    let rc = server().handle("nonsense", &Json::Null, &mut output).unwrap();
+    assert_eq!(rc, Err((-32601, "method not found: nonsense".to_string())));
+    let params = json::object(vec![("textDocument", json::object(vec![
+      ("uri", json::string("file:///other.cry"))]))]);
+    let rc = server().handle("textDocument/hover", &params, &mut output).unwrap();
+    assert_eq!(rc.map_err(|e| e.0), Err(-32602));
+  }
+}
@@ -3,38 +3,454 @@ extern crate curry;
 use std::env;
 use std::process;
 use std::fs::File;
+use std::io;
+use std::io::BufWriter;
 use std::io::prelude::*;
 
 use curry::tokenizer;
 use curry::parser;
 use curry::evaluator;
+use curry::host;
+use curry::repl;
+use curry::dump;
+use curry::testing;
+use curry::golden;
+use curry::lint;
+use curry::infer;
+use curry::lsp;
+use curry::debugger;
+use curry::trace;
+use curry::profile;
+use curry::coverage;
+
+use curry::encoding::Evaluation;
+use curry::encoding::ListEval;
+
+const USAGE: &str = "\
+usage: curry [options] [<file> | -e <code> | -] [args...]
+       curry test [--allow-fs] [--filter <text>] [--coverage]
+                  [--coverage-lcov <file>] [<file or dir>...]
+       curry golden [--allow-fs] [--bless] [<file or dir>...]
+       curry lint [<file or dir>...]
+       curry check [--report] [<file or dir>...]
+       curry lsp
+
+  <file>          run source file
+  -e <code>       run code given on the command line
+  -               read source from stdin
+  (none)          interactive REPL
+
+options:
+  --allow-fs      let the program read and write files
+  --dump-tokens   print tokens instead of running
+  --dump-ast      print parse tree instead of running
+  --debug         run in the step debugger (stops at the first call)
+  --break <name | line>
+                  run in the debugger, stopping at calls to a function or
+                  on a line (can be given more than once)
+  --trace         show each call with its arguments and result (on stderr)
+  --trace-only <name,...>
+                  only trace calls to these functions
+  --trace-depth <n>
+                  only trace calls less than n deep
+  --trace-file <file>
+                  write the trace to a file instead
+  --coverage      show source annotated with how often each line ran (on
+                  stderr)
+  --coverage-lcov <file>
+                  write coverage data in LCOV format to a file
+  --profile       show call counts and times per function (on stderr)
+  --profile-folded <file>
+                  also write folded stacks (for flamegraphs) to a file
+  --profile-chrome <file>
+                  also write Chrome trace events (JSON) to a file
+  -h, --help      show this message
+
+Anything after the source is passed to the program as _
+
+curry test runs definitions marked with a #[test] comment in the given files
+(or .cry files in the given directories, default: current directory); with
+--filter, only tests whose names contain <text>; --coverage and --coverage-lcov
+work as they do for running a program
+
+curry golden runs each file and compares its output with foo.expected next to
+foo.cry, showing a diff for any that don't match; --bless (re)writes the
+.expected files from the current output instead
+
+curry lint checks files for likely mistakes without running them
+
+curry check infers what each definition needs from its arguments and what it
+returns, and reports calls with the wrong number or types of arguments;
+--report also prints what was inferred for each definition
+
+curry lsp runs a language server on stdin/stdout for editors";
+
+enum Source {
+  File(String), Inline(String), Stdin
+}
+
+enum Dump {
+  Tokens, Ast
+}
+
+struct Options {
+  source: Option<Source>,
+  dump: Option<Dump>,
+  debug: bool,
+  breakpoints: Vec<debugger::Breakpoint>,
+  trace: Option<trace::Options>,
+  profile: Option<profile::Options>,
+  coverage: Option<coverage::Options>,
+  args: Vec<String>
+}
+
+fn usage_error(msg: &str) -> ! {
+  println!("{}\n\n{}", msg, USAGE);
+  process::exit(1);
+}
+
+fn trace_options(opts: &mut Options) -> &mut trace::Options {
+  opts.trace.get_or_insert_with(|| trace::Options {
+    names: Vec::new(), max_depth: None, output: trace::stderr()
+  })
+}
+
+fn profile_options(opts: &mut Options) -> &mut profile::Options {
+  opts.profile.get_or_insert(profile::Options { folded: None, chrome: None })
+}
+
+// --coverage or --coverage-lcov <file> (at args[index]); returns the index of
+// the last argument used
+fn coverage_flag(args: &[String], index: usize,
+                 options: &mut Option<coverage::Options>) -> usize {
+  let options = options.get_or_insert(coverage::Options { report: false, lcov: None });
+  if args[index] == "--coverage" {
+    options.report = true;
+    return index;
+  }
+  if index + 1 >= args.len() {
+    usage_error("--coverage-lcov expects a file name");
+  }
+  options.lcov = Some(args[index + 1].clone());
+  index + 1
+}
+
+// Options come first, then the source; anything after that belongs to the
+// program itself
+fn parse_args(args: &[String]) -> Options {
+  let mut opts = Options { source: None, dump: None, debug: false,
+                           breakpoints: Vec::new(), trace: None,
+                           profile: None, coverage: None, args: Vec::new() };
+  let mut index = 0;
+  while index < args.len() && opts.source.is_none() {
+    match &*args[index] {
+      "--allow-fs" => host::grant_filesystem(true),
+      "--dump-tokens" => opts.dump = Some(Dump::Tokens),
+      "--dump-ast" => opts.dump = Some(Dump::Ast),
+      "--debug" => opts.debug = true,
+      "--break" => {
+        index += 1;
+        if index >= args.len() {
+          usage_error("--break expects a function name or line number");
+        }
+        opts.debug = true;
+        opts.breakpoints.push(debugger::breakpoint(&args[index]));
+      },
+      "--trace" => {
+        trace_options(&mut opts);
+      },
+      "--trace-only" | "--trace-depth" | "--trace-file" => {
+        let flag = &*args[index];
+        index += 1;
+        if index >= args.len() {
+          usage_error(&format!("{} expects a value", flag));
+        }
+        let value = &args[index];
+        let options = trace_options(&mut opts);
+        match flag {
+          "--trace-only" => {
+            options.names.extend(value.split(',').map(|n| n.to_string()));
+          },
+          "--trace-depth" => {
+            match value.parse::<usize>() {
+              Ok(n) => options.max_depth = Some(n),
+              Err(_) => usage_error(&format!("bad depth for --trace-depth: {}", value)),
+            }
+          },
+          _ => {
+            match File::create(value) {
+              Ok(f) => options.output = Box::new(BufWriter::new(f)),
+              Err(e) => {
+                println!("unable to create trace file {}: {}", value, e);
+                process::exit(1);
+              },
+            }
+          },
+        }
+      },
+      "--profile" => {
+        profile_options(&mut opts);
+      },
+      "--profile-folded" | "--profile-chrome" => {
+        let flag = &*args[index];
+        index += 1;
+        if index >= args.len() {
+          usage_error(&format!("{} expects a file name", flag));
+        }
+        let filename = Some(args[index].clone());
+        let options = profile_options(&mut opts);
+        if flag == "--profile-folded" {
+          options.folded = filename;
+        } else {
+          options.chrome = filename;
+        }
+      },
+      "--coverage" | "--coverage-lcov" => {
+        index = coverage_flag(args, index, &mut opts.coverage);
+      },
+      "-h" | "--help" => {
+        println!("{}", USAGE);
+        process::exit(0);
+      },
+      "-e" => {
+        index += 1;
+        if index >= args.len() {
+          usage_error("-e expects code to run");
+        }
+        opts.source = Some(Source::Inline(args[index].clone()));
+      },
+      "-" => opts.source = Some(Source::Stdin),
+      arg if arg.starts_with('-') => {
+        usage_error(&format!("unknown option: {}", arg));
+      },
+      arg => opts.source = Some(Source::File(arg.to_string())),
+    }
+    index += 1;
+  }
+  opts.args = args[index..].to_vec();
+  opts
+}
+
+fn run_tests(args: &[String]) {
+  let mut paths = Vec::new();
+  let mut filter = None;
+  let mut covering = None;
+  let mut index = 0;
+  while index < args.len() {
+    match &*args[index] {
+      "--allow-fs" => host::grant_filesystem(true),
+      "--coverage" | "--coverage-lcov" => {
+        index = coverage_flag(args, index, &mut covering);
+      },
+      "--filter" => {
+        index += 1;
+        if index >= args.len() {
+          usage_error("--filter expects text to match");
+        }
+        filter = Some(&*args[index]);
+      },
+      arg if arg.starts_with('-') => {
+        usage_error(&format!("unknown option: {}", arg));
+      },
+      arg => paths.push(arg.to_string()),
+    }
+    index += 1;
+  }
+  if paths.is_empty() {
+    paths.push(".".to_string());
+  }
+  let covered = covering.is_some();
+  if let Some(options) = covering {
+    coverage::start(options);
+  }
+  let summary = testing::run(&paths, filter);
+  if covered {
+    coverage::stop();
+  }
+  if summary.failed > 0 {
+    process::exit(1);
+  }
+}
+
+fn run_golden(args: &[String]) {
+  let mut paths = Vec::new();
+  let mut bless = false;
+  for arg in args {
+    match &**arg {
+      "--allow-fs" => host::grant_filesystem(true),
+      "--bless" => bless = true,
+      arg if arg.starts_with('-') => {
+        usage_error(&format!("unknown option: {}", arg));
+      },
+      arg => paths.push(arg.to_string()),
+    }
+  }
+  if paths.is_empty() {
+    paths.push(".".to_string());
+  }
+  let summary = golden::run(&paths, bless);
+  if summary.failed > 0 {
+    process::exit(1);
+  }
+}
+
+fn run_lint(args: &[String]) {
+  let mut paths = Vec::new();
+  for arg in args {
+    if arg.starts_with('-') {
+      usage_error(&format!("unknown option: {}", arg));
+    }
+    paths.push(arg.clone());
+  }
+  if paths.is_empty() {
+    paths.push(".".to_string());
+  }
+  if lint::run(&paths) > 0 {
+    process::exit(1);
+  }
+}
+
+fn run_check(args: &[String]) {
+  let mut paths = Vec::new();
+  let mut report = false;
+  for arg in args {
+    match &**arg {
+      "--report" => report = true,
+      _ if arg.starts_with('-') => usage_error(&format!("unknown option: {}", arg)),
+      _ => paths.push(arg.clone()),
+    }
+  }
+  if paths.is_empty() {
+    paths.push(".".to_string());
+  }
+  if infer::run(&paths, report) > 0 {
+    process::exit(1);
+  }
+}
+
+fn read_source(source: &Source) -> String {
+  let mut rc = String::new();
+  match *source {
+    Source::File(ref filename) => {
+      match File::open(filename) {
+        Ok(mut file) => {
+          if let Err(e) = file.read_to_string(&mut rc) {
+            println!("failed to read source file {}: {}", filename, e);
+            process::exit(1);
+          }
+        },
+        Err(e) => {
+          println!("failed to open source file {}: {}", filename, e);
+          process::exit(1);
+        },
+      }
+      host::set_script_path(Some(filename.clone()));
+    },
+    Source::Inline(ref code) => rc = code.clone(),
+    Source::Stdin => {
+      if let Err(e) = io::stdin().read_to_string(&mut rc) {
+        println!("failed to read source from stdin: {}", e);
+        process::exit(1);
+      }
+    },
+  }
+  // The tokenizer wants the last line terminated
+  if !rc.ends_with('\n') {
+    rc.push('\n');
+  }
+  rc
+}
 
 fn main() {
   let args: Vec<String> = env::args().collect();
+  if args.len() > 1 && args[1] == "test" {
+    run_tests(&args[2..]);
+    return;
+  }
+  if args.len() > 1 && args[1] == "golden" {
+    run_golden(&args[2..]);
+    return;
+  }
+  if args.len() > 1 && args[1] == "lint" {
+    run_lint(&args[2..]);
+    return;
+  }
+  if args.len() > 1 && args[1] == "lsp" {
+    process::exit(lsp::run());
+  }
+  if args.len() > 1 && args[1] == "check" {
+    run_check(&args[2..]);
+    return;
+  }
+  let opts = parse_args(&args[1..]);
 
-  // TODO: better command line
-  if args.len() != 2 {
-    println!("Incorrect number of arguments: expecting source file as argument");
-    process::exit(0);
-  }
-  let filename = &args[1];
-
-  match File::open(&filename) {
-    Ok(mut file) => {
-      let mut source = String::new();
-      match &file.read_to_string(&mut source) {
-        &Ok(_) => {
-          let tokens = tokenizer::tokenize(&source);
-          let block = parser::parse(&tokens);
-          evaluator::evaluate(&block);
-        },
-        _ => {
-          panic!("failed to read source file");
-        }
+  let source = match opts.source {
+    Some(ref source) => read_source(source),
+    None => {
+      if opts.dump.is_some() {
+        usage_error("nothing to dump without a source");
       }
+      repl::run();
+      return;
     },
-    _ => {
-      panic!("failed to open source file");
+  };
+
+  let tokens = match tokenizer::tokenize(&source) {
+    Ok(tokens) => tokens,
+    Err(e) => {
+      println!("{}", e);
+      process::exit(1);
     },
+  };
+  if let Some(Dump::Tokens) = opts.dump {
+    println!("{}", dump::tokens(&tokens));
+    return;
+  }
+  let block = match parser::parse(&tokens) {
+    Ok(block) => block,
+    Err(e) => {
+      println!("{}", e);
+      process::exit(1);
+    },
+  };
+  if let Some(Dump::Ast) = opts.dump {
+    println!("{}", dump::tree(&block));
+    return;
+  }
+
+  let mut param = ListEval { items: Vec::new() };
+  for arg in &opts.args {
+    param.items.push(Evaluation::String(arg.clone()));
+  }
+  if opts.debug {
+    debugger::start(&source, opts.breakpoints);
+  }
+  let tracing = opts.trace.is_some();
+  if let Some(options) = opts.trace {
+    trace::start(options);
+  }
+  let profiling = opts.profile.is_some();
+  if let Some(options) = opts.profile {
+    profile::start(options);
+  }
+  let covering = opts.coverage.is_some();
+  if let Some(options) = opts.coverage {
+    coverage::start(options);
+    let name = match opts.source {
+      Some(Source::File(ref filename)) => filename.clone(),
+      Some(Source::Inline(_)) => "-e".to_string(),
+      _ => "-".to_string(),
+    };
+    coverage::add_file(&name, &source, &block);
+  }
+  evaluator::evaluate(&block, &param);
+  if tracing {
+    trace::stop();
+  }
+  if profiling {
+    profile::stop();
+  }
+  if covering {
+    coverage::stop();
   }
 }
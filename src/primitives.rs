@@ -1,13 +1,28 @@
 // Primitive functions
 
+use std::env;
+use std::num::IntErrorKind;
+
 use evaluator;
+use host;
+use symbol;
+use symbol::Symbol;
+use primitives_fs;
+use primitives_assert;
+use primitives_int;
+use primitives_bigint;
+use primitives_math;
+use primitives_bits;
+use primitives_string;
+use primitives_regex;
+use primitives_type;
 
 use encoding::Evaluation;
 use encoding::ListEval;
 use encoding::Exception;
 use encoding::ExceptionType;
 
-fn expect_args(count: usize, param: &ListEval, id: &String) ->
+pub fn expect_args(count: usize, param: &ListEval, id: &String) ->
   Option<Evaluation> {
   if count != param.items.len() {
     Some(evaluator::exception(ExceptionType::ArgError, id,
@@ -19,9 +34,75 @@ fn expect_args(count: usize, param: &ListEval, id: &String) ->
   }
 }
 
+// Integer arithmetic is checked; anything that doesn't fit in 64 bits raises
+// an overflow error rather than wrapping around (see primitives_int for the
+// wrapping and saturating versions)
+pub fn checked(result: Option<i64>, id: &String) -> Evaluation {
+  match result {
+    Some(n) => Evaluation::Integer(n),
+    None => evaluator::exception(ExceptionType::Overflow, id,
+                                 "integer overflow".to_string()),
+  }
+}
+
+// Truncates, raising an overflow error for anything out of range
+pub fn float_to_int(x: f64, id: &String) -> Evaluation {
+  // -2^63 is exact as a float, 2^63 - 1 isn't (and NaN is never in range)
+  if (-9223372036854775808.0..9223372036854775808.0).contains(&x) {
+    Evaluation::Integer(x as i64)
+  } else {
+    evaluator::exception(ExceptionType::Overflow, id,
+                         format!("{} doesn't fit in an integer", x))
+  }
+}
+
+pub fn truth(b: bool) -> Evaluation {
+  if b { Evaluation::True } else { Evaluation::False }
+}
+
+pub fn div_by_zero(id: &String) -> Evaluation {
+  evaluator::exception(ExceptionType::DivByZero, id,
+                       "integer division by zero".to_string())
+}
+
+// Everything handled directly below, plus , (which Call handles itself since
+// it needs its argument list unevaluated)
+pub const PRIMITIVES: [&str; 32] = [
+  ",", "int", "float", "string", "symbol", "list", "unlist", ">>", "<<", "<<<", "env",
+  "script", "+", "-", "*", "/", "%", "!", "&", "|", "?", "=", "==", ">", "<",
+  "substr", "strlen", "car", "cdr", "catch", "raise", "~",
+];
+
+// Everything that isn't handled directly below gets farmed out to these, in
+// order; each returns None for any id it doesn't know about
+type Family = fn(&String, &ListEval) -> Option<Evaluation>;
+static FAMILIES: &[(&[&str], Family)] = &[
+  (&primitives_fs::PRIMITIVES, primitives_fs::system_functions),
+  (&primitives_assert::PRIMITIVES, primitives_assert::system_functions),
+  (&primitives_int::PRIMITIVES, primitives_int::system_functions),
+  (&primitives_bigint::PRIMITIVES, primitives_bigint::system_functions),
+  (&primitives_math::PRIMITIVES, primitives_math::system_functions),
+  (&primitives_bits::PRIMITIVES, primitives_bits::system_functions),
+  (&primitives_string::PRIMITIVES, primitives_string::system_functions),
+  (&primitives_regex::PRIMITIVES, primitives_regex::system_functions),
+  (&primitives_type::PRIMITIVES, primitives_type::system_functions),
+];
+
+pub fn names() -> Vec<&'static str> {
+  let mut rc = PRIMITIVES.to_vec();
+  for family in FAMILIES {
+    rc.extend_from_slice(family.0);
+  }
+  rc
+}
+
+pub fn is_primitive(id: &str) -> bool {
+  names().contains(&id)
+}
+
 // TODO: break this up into functions?  Could abstract this substantially, too
 pub fn system_functions(id: String, param: ListEval) -> Evaluation {
-  if id != "?" && id != "catch" {
+  if id != "?" && id != "catch" && id != "assert_raises" {
     for p in &param.items {
       match p {
         &Evaluation::Exception(_) => { return p.clone(); },
@@ -31,6 +112,10 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
       }
     }
   }
+  // Big integers get handled separately, whatever the operation
+  if let Some(rc) = primitives_bigint::system_functions(&id, &param) {
+    return rc;
+  }
   match &*id {
     // Type Conversion
     "int" => {
@@ -38,16 +123,22 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         Some(e) => e,
         None => {
           match param.items[0] {
-            Evaluation::Float(x) => Evaluation::Integer(x as i64),
+            Evaluation::Integer(x) => Evaluation::Integer(x),
+            Evaluation::Float(x) => float_to_int(x, &id),
             Evaluation::String(ref s) => {
               match s.parse::<i64>() {
                 Ok(n) => Evaluation::Integer(n),
+                Err(ref e) if *e.kind() == IntErrorKind::PosOverflow ||
+                  *e.kind() == IntErrorKind::NegOverflow => {
+                  evaluator::exception(ExceptionType::Overflow, &id,
+                                       format!("{} doesn't fit in an integer", s))
+                },
                 _ => evaluator::exception(ExceptionType::ParseError, &id,
                                           format!("unable to parse string: {}", s)),
               }
             },
             _ => evaluator::exception(ExceptionType::TypeError, &id,
-                                      "float or string argument expected".to_string()),
+                                      "number or string argument expected".to_string()),
           }
         },
       }
@@ -58,6 +149,7 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         None => {
           match param.items[0] {
             Evaluation::Integer(x) => Evaluation::Float(x as f64),
+            Evaluation::Float(x) => Evaluation::Float(x),
             Evaluation::String(ref s) => {
               match s.parse::<f64>() {
                 Ok(n) => Evaluation::Float(n),
@@ -66,15 +158,84 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
               }
             },
             _ => evaluator::exception(ExceptionType::TypeError, &id,
-                                      "int or string argument expected".to_string()),
+                                      "number or string argument expected".to_string()),
           }
         },
       }
     },
+    // A symbol gives its name, without the '
     "string" => {
       match expect_args(1, &param, &id) {
         Some(e) => e,
-        None => Evaluation::String(format!("{}", param.items[0])),
+        None => {
+          match param.items[0] {
+            Evaluation::Symbol(x) => Evaluation::String(x.name()),
+            ref other => Evaluation::String(format!("{}", other)),
+          }
+        },
+      }
+    },
+    // Only names that could be written as a literal, so it always reads back
+    "symbol" => {
+      match expect_args(1, &param, &id) {
+        Some(e) => e,
+        None => {
+          match param.items[0] {
+            Evaluation::String(ref s) if symbol::valid_name(s) => {
+              Evaluation::Symbol(Symbol::intern(s))
+            },
+            Evaluation::String(ref s) => {
+              evaluator::exception(ExceptionType::ParseError, &id,
+                                   format!("not a valid symbol name: \"{}\"", s))
+            },
+            Evaluation::Symbol(_) => param.items[0].clone(),
+            _ => evaluator::exception(ExceptionType::TypeError, &id,
+                                      "string or symbol argument expected".to_string()),
+          }
+        },
+      }
+    },
+    // Strings become lists of characters, lists stay as they are, and
+    // anything else is wrapped in a list of its own
+    "list" => {
+      match expect_args(1, &param, &id) {
+        Some(e) => e,
+        None => {
+          match param.items[0] {
+            Evaluation::String(ref s) => {
+              Evaluation::List(ListEval {
+                items: s.chars().map(|c| Evaluation::String(c.to_string())).collect()
+              })
+            },
+            Evaluation::List(_) => param.items[0].clone(),
+            ref other => Evaluation::List(ListEval { items: vec![other.clone()] }),
+          }
+        },
+      }
+    },
+    // The other way: a list of characters (or any strings) back to a string
+    "unlist" => {
+      match expect_args(1, &param, &id) {
+        Some(e) => e,
+        None => {
+          match param.items[0] {
+            Evaluation::List(ref list) => {
+              let mut rc = String::new();
+              for i in &list.items {
+                match *i {
+                  Evaluation::String(ref s) => rc += s,
+                  _ => {
+                    return evaluator::exception(ExceptionType::TypeError, &id,
+                                                "list of strings expected".to_string());
+                  },
+                }
+              }
+              Evaluation::String(rc)
+            },
+            _ => evaluator::exception(ExceptionType::TypeError, &id,
+                                      "list of strings expected".to_string()),
+          }
+        },
       }
     },
     // IO
@@ -84,7 +245,7 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         None => {
           match param.items[0] {
             Evaluation::String(ref s) => {
-              println!("{}", s);
+              host::write_line(s);
               // TODO: return empty list instead
               Evaluation::False
             },
@@ -94,6 +255,61 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         },
       }
     },
+    "<<" => {
+      match expect_args(0, &param, &id) {
+        Some(e) => e,
+        None => {
+          match host::read_line() {
+            Ok(Some(s)) => Evaluation::String(s),
+            // EOF is an empty list, so it can't be confused with a blank line
+            Ok(None) => Evaluation::List(ListEval { items: Vec::new() }),
+            Err(e) => evaluator::exception(ExceptionType::RuntimeError, &id,
+                                           format!("unable to read input: {}", e)),
+          }
+        },
+      }
+    },
+    "<<<" => {
+      match expect_args(0, &param, &id) {
+        Some(e) => e,
+        None => {
+          match host::read_all() {
+            Ok(s) => Evaluation::String(s),
+            Err(e) => evaluator::exception(ExceptionType::RuntimeError, &id,
+                                           format!("unable to read input: {}", e)),
+          }
+        },
+      }
+    },
+    // HOST
+    "env" => {
+      match expect_args(1, &param, &id) {
+        Some(e) => e,
+        None => {
+          match param.items[0] {
+            Evaluation::String(ref s) => {
+              match env::var(s) {
+                Ok(value) => Evaluation::String(value),
+                _ => Evaluation::List(ListEval { items: Vec::new() }),
+              }
+            },
+            _ => evaluator::exception(ExceptionType::TypeError, &id,
+                                      "string argument expected".to_string()),
+          }
+        },
+      }
+    },
+    "script" => {
+      match expect_args(0, &param, &id) {
+        Some(e) => e,
+        None => {
+          match host::script_path() {
+            Some(path) => Evaluation::String(path),
+            None => Evaluation::List(ListEval { items: Vec::new() }),
+          }
+        },
+      }
+    },
     // MATH (plus appending things)
     "+" => {
       match expect_args(2, &param, &id) {
@@ -102,7 +318,7 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
           match param.items[0] {
             Evaluation::Integer(x) => {
               match param.items[1] {
-                Evaluation::Integer(y) => Evaluation::Integer(x + y),
+                Evaluation::Integer(y) => checked(x.checked_add(y), &id),
                 Evaluation::Float(y) => Evaluation::Float(x as f64 + y),
                 _ => evaluator::exception(ExceptionType::TypeMismatch, &id,
                                           "mismatched argument types".to_string()),
@@ -156,7 +372,7 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
           match param.items[0] {
             Evaluation::Integer(x) => {
               match param.items[1] {
-                Evaluation::Integer(y) => Evaluation::Integer(x - y),
+                Evaluation::Integer(y) => checked(x.checked_sub(y), &id),
                 Evaluation::Float(y) => Evaluation::Float(x as f64 - y),
                 _ => evaluator::exception(ExceptionType::TypeError, &id,
                                           "numeric arguments expected".to_string()),
@@ -183,7 +399,7 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
           match param.items[0] {
             Evaluation::Integer(x) => {
               match param.items[1] {
-                Evaluation::Integer(y) => Evaluation::Integer(x * y),
+                Evaluation::Integer(y) => checked(x.checked_mul(y), &id),
                 Evaluation::Float(y) => Evaluation::Float(x as f64 * y),
                 _ => evaluator::exception(ExceptionType::TypeError, &id,
                                           "numeric arguments expected".to_string()),
@@ -208,10 +424,10 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         Some(e) => e,
         None => {
           match param.items[0] {
-            // TODO: handle division by zero with proper error
             Evaluation::Integer(x) => {
               match param.items[1] {
-                Evaluation::Integer(y) => Evaluation::Integer(x / y),
+                Evaluation::Integer(0) => div_by_zero(&id),
+                Evaluation::Integer(y) => checked(x.checked_div(y), &id),
                 Evaluation::Float(y) => Evaluation::Float(x as f64 / y),
                 _ => evaluator::exception(ExceptionType::TypeError, &id,
                                           "numeric arguments expected".to_string()),
@@ -236,10 +452,10 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         Some(e) => e,
         None => {
           match param.items[0] {
-            // TODO: handle division by zero with proper error
             Evaluation::Integer(x) => {
               match param.items[1] {
-                Evaluation::Integer(y) => Evaluation::Integer(x % y),
+                Evaluation::Integer(0) => div_by_zero(&id),
+                Evaluation::Integer(y) => checked(x.checked_rem(y), &id),
                 _ => evaluator::exception(ExceptionType::TypeError, &id,
                                           "integer arguments expected".to_string()),
               }
@@ -386,6 +602,12 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
                 _ => Evaluation::False,
               }
             },
+            Evaluation::Symbol(x) => {
+              match param.items[1] {
+                Evaluation::Symbol(y) => truth(x == y),
+                _ => Evaluation::False,
+              }
+            },
             Evaluation::List(ref x) => {
               match param.items[1] {
                 Evaluation::List(ref y) => {
@@ -416,6 +638,23 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         },
       }
     },
+    // Numeric equality: like < and >, an int compared with a float is
+    // promoted to float
+    "==" => {
+      match expect_args(2, &param, &id) {
+        Some(e) => e,
+        None => {
+          match (&param.items[0], &param.items[1]) {
+            (&Evaluation::Integer(x), &Evaluation::Integer(y)) => truth(x == y),
+            (&Evaluation::Integer(x), &Evaluation::Float(y)) => truth(x as f64 == y),
+            (&Evaluation::Float(x), &Evaluation::Integer(y)) => truth(x == y as f64),
+            (&Evaluation::Float(x), &Evaluation::Float(y)) => truth(x == y),
+            _ => evaluator::exception(ExceptionType::TypeError, &id,
+                                      "numeric arguments expected".to_string()),
+          }
+        },
+      }
+    },
     ">" => {
       match expect_args(2, &param, &id) {
         Some(e) => e,
@@ -532,7 +771,7 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
                       let chars = s.chars();
                       if start as usize >= s.len() {
                         Evaluation::String("".to_string())
-                      } else if (start + len) as usize >= s.len() {
+                      } else if start.saturating_add(len) as usize >= s.len() {
                         let rc = chars.skip(start as usize).take(s.len() - start as usize).collect();
                         Evaluation::String(rc)
                       } else {
@@ -642,7 +881,14 @@ pub fn system_functions(id: String, param: ListEval) -> Evaluation {
         },
       }
     },
-    _ => evaluator::exception(ExceptionType::UndefError, &id,
-                              "function is not defined in scope".to_string()),
+    _ => {
+      for family in FAMILIES {
+        if let Some(eval) = (family.1)(&id, &param) {
+          return eval;
+        }
+      }
+      evaluator::exception(ExceptionType::UndefError, &id,
+                           "function is not defined in scope".to_string())
+    },
   }
 }
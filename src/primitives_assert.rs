@@ -0,0 +1,150 @@
+// Assertion primitives for tests; failures raise assertion errors
+
+use evaluator;
+use primitives;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+pub const PRIMITIVES: [&str; 3] = ["assert", "assert_eq", "assert_raises"];
+
+fn expect_arg_range(min: usize, max: usize, param: &ListEval, id: &String) ->
+  Option<Evaluation> {
+  let len = param.items.len();
+  if len < min || len > max {
+    Some(evaluator::exception(ExceptionType::ArgError, id,
+                              format!("expected argument list of length {} or {} but got {}",
+                                      min, max, len)))
+  } else {
+    None
+  }
+}
+
+// The optional message is always the last argument after the required ones
+fn message(required: usize, param: &ListEval, id: &String) ->
+  Result<String, Evaluation> {
+  if param.items.len() <= required {
+    return Ok("".to_string());
+  }
+  match param.items[required] {
+    Evaluation::String(ref s) => Ok(format!("{} : ", s)),
+    _ => Err(evaluator::exception(ExceptionType::TypeError, id,
+                                  "message must be string".to_string())),
+  }
+}
+
+fn equal(a: &Evaluation, b: &Evaluation) -> bool {
+  let cmp = ListEval { items: vec![a.clone(), b.clone()] };
+  matches!(primitives::system_functions("=".to_string(), cmp),
+           Evaluation::True)
+}
+
+// Where actual first stops matching expected (as a path of list indexes like
+// [2][0]), and the values found there
+fn first_difference(actual: &Evaluation, expected: &Evaluation, path: String) ->
+  Option<(String, Evaluation, Evaluation)> {
+  match (actual, expected) {
+    (Evaluation::List(a), Evaluation::List(e)) => {
+      for n in 0..a.items.len().min(e.items.len()) {
+        let diff = first_difference(&a.items[n], &e.items[n],
+                                    format!("{}[{}]", path, n));
+        if diff.is_some() {
+          return diff;
+        }
+      }
+      // Everything they have in common matches, so the list itself is the
+      // difference (if there is one)
+      if a.items.len() == e.items.len() {
+        return None;
+      }
+    },
+    _ => {
+      if equal(actual, expected) {
+        return None;
+      }
+    },
+  }
+  Some((path, actual.clone(), expected.clone()))
+}
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  let rc = match &**id {
+    "assert" => {
+      if let Some(e) = expect_arg_range(1, 2, param, id) {
+        return Some(e);
+      }
+      let msg = match message(1, param, id) {
+        Ok(msg) => msg,
+        Err(e) => return Some(e),
+      };
+      match param.items[0] {
+        Evaluation::True => Evaluation::True,
+        Evaluation::False => {
+          evaluator::exception(ExceptionType::AssertionError, id,
+                               format!("{}assertion failed", msg))
+        },
+        _ => evaluator::exception(ExceptionType::TypeError, id,
+                                  "boolean argument expected".to_string()),
+      }
+    },
+    "assert_eq" => {
+      if let Some(e) = expect_arg_range(2, 3, param, id) {
+        return Some(e);
+      }
+      let msg = match message(2, param, id) {
+        Ok(msg) => msg,
+        Err(e) => return Some(e),
+      };
+      match first_difference(&param.items[0], &param.items[1], "".to_string()) {
+        None => Evaluation::True,
+        Some((path, actual, expected)) => {
+          let location = if path.is_empty() {
+            "".to_string()
+          } else {
+            format!(" at {}", path)
+          };
+          evaluator::exception(ExceptionType::AssertionError, id,
+                               format!("{}values differ{} : expected {}, got {}",
+                                       msg, location, expected, actual))
+        },
+      }
+    },
+    "assert_raises" => {
+      if let Some(e) = expect_arg_range(1, 2, param, id) {
+        return Some(e);
+      }
+      // Exceptions aren't passed through automatically for this one, but the
+      // expected type still shouldn't be one
+      if param.items.len() > 1 {
+        if let Evaluation::Exception(_) = param.items[1] {
+          return Some(param.items[1].clone());
+        }
+      }
+      match param.items[0] {
+        Evaluation::Exception(ref ex) => {
+          match param.items.get(1) {
+            None => Evaluation::List(ex.to_list()),
+            Some(Evaluation::String(flavor)) => {
+              if *flavor == ex.flavor.to_string() {
+                Evaluation::List(ex.to_list())
+              } else {
+                evaluator::exception(ExceptionType::AssertionError, id,
+                                     format!("expected {} exception, got {} : {}",
+                                             flavor, ex.flavor, ex.payload))
+              }
+            },
+            Some(_) => evaluator::exception(ExceptionType::TypeError, id,
+                                            "exception type must be string".to_string()),
+          }
+        },
+        ref eval => {
+          evaluator::exception(ExceptionType::AssertionError, id,
+                               format!("expected exception, got {}", eval))
+        },
+      }
+    },
+    _ => return None,
+  };
+  Some(rc)
+}
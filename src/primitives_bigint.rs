@@ -0,0 +1,172 @@
+// Big integers: bigint makes one (from an int, float or string of digits), as
+// does any integer literal too big for an int.  Arithmetic and comparisons
+// with a big integer on one side and an int or big integer on the other give
+// big integers; with a float, they work like ints do with floats.  Plain ints
+// never turn into big integers by themselves (they raise an overflow error
+// instead), so programs that need them ask for them
+
+use evaluator;
+use primitives::expect_args;
+use primitives::div_by_zero;
+use primitives::truth;
+
+use bigint::BigInt;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+pub const PRIMITIVES: [&str; 1] = ["bigint"];
+
+// Biggest result pow will work out (about 315,000 digits); anything bigger
+// would take so long it might as well have hung
+const MAX_POWER_BITS: u64 = 1 << 20;
+
+pub fn big(e: &Evaluation) -> Option<BigInt> {
+  match *e {
+    Evaluation::Integer(x) => Some(BigInt::from_i64(x)),
+    Evaluation::BigInt(ref x) => Some(x.clone()),
+    _ => None,
+  }
+}
+
+fn float(e: &Evaluation) -> Option<f64> {
+  match *e {
+    Evaluation::Integer(x) => Some(x as f64),
+    Evaluation::BigInt(ref x) => Some(x.to_f64()),
+    Evaluation::Float(x) => Some(x),
+    _ => None,
+  }
+}
+
+fn convert(id: &String, param: &ListEval) -> Evaluation {
+  if let Some(e) = expect_args(1, param, id) {
+    return e;
+  }
+  match param.items[0] {
+    Evaluation::Integer(x) => Evaluation::BigInt(BigInt::from_i64(x)),
+    Evaluation::BigInt(ref x) => Evaluation::BigInt(x.clone()),
+    Evaluation::Float(x) => {
+      match BigInt::from_f64(x) {
+        Some(n) => Evaluation::BigInt(n),
+        None => evaluator::exception(ExceptionType::Overflow, id,
+                                     format!("{} isn't a finite number", x)),
+      }
+    },
+    Evaluation::String(ref s) => {
+      match BigInt::parse(s) {
+        Some(n) => Evaluation::BigInt(n),
+        None => evaluator::exception(ExceptionType::ParseError, id,
+                                     format!("unable to parse string: {}", s)),
+      }
+    },
+    _ => evaluator::exception(ExceptionType::TypeError, id,
+                              "number or string argument expected".to_string()),
+  }
+}
+
+fn arithmetic(id: &String, param: &ListEval) -> Evaluation {
+  let (a, b) = (&param.items[0], &param.items[1]);
+  if let (Some(x), Some(y)) = (big(a), big(b)) {
+    return match &**id {
+      "+" => Evaluation::BigInt(x.add(&y)),
+      "-" => Evaluation::BigInt(x.sub(&y)),
+      "*" => Evaluation::BigInt(x.mul(&y)),
+      _ => {
+        match x.div_rem(&y) {
+          Some((q, _)) if id == "/" => Evaluation::BigInt(q),
+          Some((_, r)) => Evaluation::BigInt(r),
+          None => div_by_zero(id),
+        }
+      },
+    };
+  }
+  match (float(a), float(b)) {
+    (Some(x), Some(y)) if id != "%" => {
+      Evaluation::Float(match &**id {
+        "+" => x + y,
+        "-" => x - y,
+        "*" => x * y,
+        _ => x / y,
+      })
+    },
+    _ if id == "+" => evaluator::exception(ExceptionType::TypeMismatch, id,
+                                           "mismatched argument types".to_string()),
+    _ if id == "%" => evaluator::exception(ExceptionType::TypeError, id,
+                                           "integer arguments expected".to_string()),
+    _ => evaluator::exception(ExceptionType::TypeError, id,
+                              "numeric arguments expected".to_string()),
+  }
+}
+
+fn compare(id: &String, param: &ListEval) -> Evaluation {
+  let (a, b) = (&param.items[0], &param.items[1]);
+  if let (Some(x), Some(y)) = (big(a), big(b)) {
+    return truth(match &**id { "<" => x < y, ">" => x > y, _ => x == y });
+  }
+  match (float(a), float(b)) {
+    (Some(x), Some(y)) => truth(match &**id { "<" => x < y, ">" => x > y, _ => x == y }),
+    _ => evaluator::exception(ExceptionType::TypeError, id,
+                              "numeric arguments expected".to_string()),
+  }
+}
+
+// Handles bigint, and everything else it knows about when one of the
+// arguments is a big integer
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  if id == "bigint" {
+    return Some(convert(id, param));
+  }
+  if !param.items.iter().any(|p| matches!(*p, Evaluation::BigInt(_))) {
+    return None;
+  }
+  let count = match &**id {
+    "+" | "-" | "*" | "/" | "%" | "<" | ">" | "==" | "=" => 2,
+    "int" | "float" | "abs" => 1,
+    // Only a big integer to a non-negative int power stays a big integer;
+    // anything else to do with powers is left to floats
+    "pow" => {
+      return match (param.items.len(), &param.items[0], param.items.get(1)) {
+        (2, Evaluation::BigInt(x), Some(&Evaluation::Integer(e))) if e >= 0 => {
+          // 0, 1 and -1 stay small however big the power is
+          let size = if x.bits() <= 1 { 0 } else { x.bits().saturating_mul(e as u64) };
+          if size > MAX_POWER_BITS {
+            return Some(evaluator::exception(ExceptionType::Overflow, id,
+                                             "integer overflow".to_string()));
+          }
+          Some(Evaluation::BigInt(x.pow(e as u64)))
+        },
+        _ => None,
+      };
+    },
+    _ => return None,
+  };
+  if let Some(e) = expect_args(count, param, id) {
+    return Some(e);
+  }
+  Some(match &**id {
+    "+" | "-" | "*" | "/" | "%" => arithmetic(id, param),
+    "<" | ">" | "==" => compare(id, param),
+    // Big integers are integers, so they can equal ints, but never floats
+    "=" => {
+      match (big(&param.items[0]), big(&param.items[1])) {
+        (Some(x), Some(y)) => truth(x == y),
+        _ => Evaluation::False,
+      }
+    },
+    "abs" => {
+      match param.items[0] {
+        Evaluation::BigInt(ref x) => Evaluation::BigInt(x.abs()),
+        ref other => other.clone(),
+      }
+    },
+    "int" => {
+      match big(&param.items[0]).and_then(|n| n.to_i64()) {
+        Some(n) => Evaluation::Integer(n),
+        None => evaluator::exception(ExceptionType::Overflow, id,
+                                     format!("{} doesn't fit in an integer", param.items[0])),
+      }
+    },
+    _ => Evaluation::Float(float(&param.items[0]).unwrap_or(0.0)),
+  })
+}
@@ -0,0 +1,61 @@
+// Bitwise operations on ints, treating them as 64-bit two's complement.
+// Shifts take an amount from 0 to 63; anything else is a domain error rather
+// than whatever the hardware would do with it
+
+use evaluator;
+use primitives::expect_args;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+pub const PRIMITIVES: [&str; 10] = [
+  "band", "bor", "bxor", "bnot", "shl", "ashr", "lshr", "popcount", "clz", "ctz",
+];
+
+fn ints(param: &ListEval, id: &String) -> Result<Vec<i64>, Evaluation> {
+  let mut rc = Vec::new();
+  for p in &param.items {
+    match *p {
+      Evaluation::Integer(x) => rc.push(x),
+      _ => return Err(evaluator::exception(ExceptionType::TypeError, id,
+                                           "integer arguments expected".to_string())),
+    }
+  }
+  Ok(rc)
+}
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  let count = match &**id {
+    "band" | "bor" | "bxor" | "shl" | "ashr" | "lshr" => 2,
+    "bnot" | "popcount" | "clz" | "ctz" => 1,
+    _ => return None,
+  };
+  if let Some(e) = expect_args(count, param, id) {
+    return Some(e);
+  }
+  let args = match ints(param, id) {
+    Ok(args) => args,
+    Err(e) => return Some(e),
+  };
+  let x = args[0];
+  if id == "shl" || id == "ashr" || id == "lshr" {
+    let n = args[1];
+    if !(0..64).contains(&n) {
+      return Some(evaluator::exception(ExceptionType::DomainError, id,
+                                       format!("shift amount must be from 0 to 63, not {}", n)));
+    }
+  }
+  Some(Evaluation::Integer(match &**id {
+    "band" => x & args[1],
+    "bor" => x | args[1],
+    "bxor" => x ^ args[1],
+    "bnot" => !x,
+    "shl" => x << args[1],
+    "ashr" => x >> args[1],
+    "lshr" => ((x as u64) >> args[1]) as i64,
+    "popcount" => i64::from(x.count_ones()),
+    "clz" => i64::from(x.leading_zeros()),
+    _ => i64::from(x.trailing_zeros()),
+  }))
+}
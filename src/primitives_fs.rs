@@ -0,0 +1,208 @@
+// Filesystem primitives; these are all off unless the host grants access
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+
+use evaluator;
+use host;
+use primitives::expect_args;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+fn io_error(id: &String, path: &str, e: io::Error) -> Evaluation {
+  evaluator::exception(ExceptionType::IOError, id, format!("{}: {}", path, e))
+}
+
+// Everything here takes a path as its first argument, so check that (and the
+// argument count) up front
+fn get_path(count: usize, param: &ListEval, id: &String) ->
+  Result<String, Evaluation> {
+  if let Some(e) = expect_args(count, param, id) {
+    return Err(e);
+  }
+  match param.items[0] {
+    Evaluation::String(ref s) => Ok(s.clone()),
+    _ => Err(evaluator::exception(ExceptionType::TypeError, id,
+                                  "first argument must be string path".to_string())),
+  }
+}
+
+fn write_file(id: &String, param: &ListEval, append: bool) -> Evaluation {
+  let path = match get_path(2, param, id) {
+    Ok(path) => path,
+    Err(e) => return e,
+  };
+  let contents = match param.items[1] {
+    Evaluation::String(ref s) => s,
+    _ => return evaluator::exception(ExceptionType::TypeError, id,
+                                     "second argument must be string".to_string()),
+  };
+  let file = OpenOptions::new().write(true).create(true)
+    .append(append).truncate(!append).open(&path);
+  match file.and_then(|mut f| f.write_all(contents.as_bytes())) {
+    Ok(_) => Evaluation::List(ListEval { items: Vec::new() }),
+    Err(e) => io_error(id, &path, e),
+  }
+}
+
+pub const PRIMITIVES: [&str; 6] =
+  ["fread", "flines", "fwrite", "fappend", "fexists", "dir"];
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  if !PRIMITIVES.contains(&&**id) {
+    return None;
+  }
+  if !host::filesystem_allowed() {
+    return Some(evaluator::exception(ExceptionType::IOError, id,
+                                     "filesystem access is disabled".to_string()));
+  }
+  let rc = match &**id {
+    "fread" => {
+      match get_path(1, param, id) {
+        Ok(path) => {
+          match fs::read_to_string(&path) {
+            Ok(s) => Evaluation::String(s),
+            Err(e) => io_error(id, &path, e),
+          }
+        },
+        Err(e) => e,
+      }
+    },
+    "flines" => {
+      match get_path(1, param, id) {
+        Ok(path) => {
+          match fs::read_to_string(&path) {
+            Ok(s) => {
+              let mut list = ListEval { items: Vec::new() };
+              for line in s.lines() {
+                list.items.push(Evaluation::String(line.to_string()));
+              }
+              Evaluation::List(list)
+            },
+            Err(e) => io_error(id, &path, e),
+          }
+        },
+        Err(e) => e,
+      }
+    },
+    "fwrite" => write_file(id, param, false),
+    "fappend" => write_file(id, param, true),
+    "fexists" => {
+      match get_path(1, param, id) {
+        Ok(path) => {
+          if fs::metadata(&path).is_ok() {
+            Evaluation::True
+          } else {
+            Evaluation::False
+          }
+        },
+        Err(e) => e,
+      }
+    },
+    "dir" => {
+      match get_path(1, param, id) {
+        Ok(path) => {
+          match fs::read_dir(&path) {
+            Ok(entries) => {
+              let mut names = Vec::new();
+              for entry in entries {
+                match entry {
+                  Ok(entry) => {
+                    names.push(entry.file_name().to_string_lossy().into_owned());
+                  },
+                  Err(e) => return Some(io_error(id, &path, e)),
+                }
+              }
+              names.sort();
+              let mut list = ListEval { items: Vec::new() };
+              for name in names {
+                list.items.push(Evaluation::String(name));
+              }
+              Evaluation::List(list)
+            },
+            Err(e) => io_error(id, &path, e),
+          }
+        },
+        Err(e) => e,
+      }
+    },
+    _ => panic!("internal error: unhandled filesystem primitive {}", id),
+  };
+  Some(rc)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+  use std::path::PathBuf;
+  use std::process;
+
+  fn call(id: &str, args: &[&str]) -> Evaluation {
+    let items = args.iter().map(|a| Evaluation::String(a.to_string())).collect();
+    system_functions(&id.to_string(), &ListEval { items }).unwrap()
+  }
+
+  // A fresh directory of our own to play in
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("curry-fs-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn disabled() {
+    host::grant_filesystem(false);
+    match call("fexists", &["."]) {
+      Evaluation::Exception(ref ex) => {
+        assert_eq!(ex.payload.to_string(), "\"fexists : filesystem access is disabled\"");
+      },
+      other => panic!("expected an exception, got {}", other),
+    }
+  }
+
+  #[test]
+  fn round_trip() {
+    host::grant_filesystem(true);
+    let dir = temp_dir("round-trip");
+    let file = dir.join("notes.txt");
+    let path = file.to_str().unwrap();
+    assert_eq!(call("fexists", &[path]).to_string(), "false");
+    assert_eq!(call("fwrite", &[path, "one\n"]).to_string(), "[]");
+    assert_eq!(call("fappend", &[path, "two\r\nthree"]).to_string(), "[]");
+    assert_eq!(call("fexists", &[path]).to_string(), "true");
+    assert_eq!(call("fread", &[path]).to_string(), "\"one\ntwo\r\nthree\"");
+    assert_eq!(call("flines", &[path]).to_string(), "[\"one\" \"two\" \"three\"]");
+    // fwrite starts again from scratch
+    call("fwrite", &[path, "four"]);
+    assert_eq!(call("fread", &[path]).to_string(), "\"four\"");
+    fs::write(dir.join("b"), "").unwrap();
+    fs::create_dir(dir.join("a")).unwrap();
+    assert_eq!(call("dir", &[dir.to_str().unwrap()]).to_string(), "[\"a\" \"b\" \"notes.txt\"]");
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn errors_carry_the_os_message() {
+    host::grant_filesystem(true);
+    let dir = temp_dir("errors");
+    let missing = dir.join("missing.txt");
+    let path = missing.to_str().unwrap();
+    let os = fs::read_to_string(&missing).unwrap_err();
+    for id in &["fread", "flines", "dir"] {
+      match call(id, &[path]) {
+        Evaluation::Exception(ref ex) => {
+          assert_eq!(ex.flavor.to_string(), "io error");
+          assert_eq!(ex.payload.to_string(), format!("\"{} : {}: {}\"", id, path, os));
+        },
+        other => panic!("expected an exception from {}, got {}", id, other),
+      }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
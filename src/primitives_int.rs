@@ -0,0 +1,44 @@
+// Integer arithmetic that doesn't raise on overflow: the wrapping versions
+// wrap around (two's complement, like C), and the saturating versions stick
+// at the largest or smallest integer.  Division by zero still raises
+
+use evaluator;
+use primitives::expect_args;
+use primitives::div_by_zero;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+pub const PRIMITIVES: [&str; 10] = [
+  "wrapping+", "wrapping-", "wrapping*", "wrapping/", "wrapping%",
+  "saturating+", "saturating-", "saturating*", "saturating/", "saturating%",
+];
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  let op: fn(i64, i64) -> i64 = match &**id {
+    "wrapping+" => i64::wrapping_add,
+    "wrapping-" => i64::wrapping_sub,
+    "wrapping*" => i64::wrapping_mul,
+    "wrapping/" => i64::wrapping_div,
+    "wrapping%" => i64::wrapping_rem,
+    "saturating+" => i64::saturating_add,
+    "saturating-" => i64::saturating_sub,
+    "saturating*" => i64::saturating_mul,
+    "saturating/" => i64::saturating_div,
+    // The only remainder that overflows is -2^63 % -1, which is really 0
+    "saturating%" => i64::wrapping_rem,
+    _ => return None,
+  };
+  if let Some(e) = expect_args(2, param, id) {
+    return Some(e);
+  }
+  Some(match (&param.items[0], &param.items[1]) {
+    (&Evaluation::Integer(_), &Evaluation::Integer(0)) if id.ends_with('/') || id.ends_with('%') => {
+      div_by_zero(id)
+    },
+    (&Evaluation::Integer(x), &Evaluation::Integer(y)) => Evaluation::Integer(op(x, y)),
+    _ => evaluator::exception(ExceptionType::TypeError, id,
+                              "integer arguments expected".to_string()),
+  })
+}
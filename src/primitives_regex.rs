@@ -0,0 +1,134 @@
+// Regular expression primitives (see regex for what patterns can do).  Like
+// the other string primitives, these take the string first, and positions
+// are in characters.  A match is a list of captures: the whole match, then
+// each group in order, with [] for groups that didn't take part
+
+use evaluator;
+use primitives::expect_args;
+use regex::Regex;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+pub const PRIMITIVES: [&str; 4] = ["re_match", "re_find_all", "re_replace", "re_split"];
+
+fn text(chars: &[char], from: usize, to: usize) -> String {
+  chars[from..to].iter().collect()
+}
+
+fn captures(chars: &[char], slots: &[Option<usize>]) -> Evaluation {
+  let items = slots.chunks(2).map(|s| match (s[0], s[1]) {
+    (Some(from), Some(to)) => Evaluation::String(text(chars, from, to)),
+    _ => Evaluation::List(ListEval { items: Vec::new() }),
+  }).collect();
+  Evaluation::List(ListEval { items })
+}
+
+// The replacement with $n swapped for group n ($$ for a dollar sign)
+fn expand(replacement: &[char], chars: &[char], slots: &[Option<usize>]) -> String {
+  let mut rc = String::new();
+  let mut i = 0;
+  while i < replacement.len() {
+    let c = replacement[i];
+    i += 1;
+    if c != '$' || i == replacement.len() {
+      rc.push(c);
+      continue;
+    }
+    if replacement[i] == '$' {
+      rc.push('$');
+      i += 1;
+      continue;
+    }
+    let start = i;
+    while i < replacement.len() && replacement[i].is_ascii_digit() {
+      i += 1;
+    }
+    let digits = text(replacement, start, i);
+    match digits.parse::<usize>() {
+      // A group number too big to exist is just a group that didn't match
+      Ok(n) => {
+        if let Some(at) = n.checked_mul(2) {
+          if let (Some(&Some(from)), Some(&Some(to))) = (slots.get(at), slots.get(at + 1)) {
+            rc += &text(chars, from, to);
+          }
+        }
+      },
+      // No digits (or far too many), so it's not a group after all
+      Err(_) => {
+        rc.push('$');
+        rc += &digits;
+      },
+    }
+  }
+  rc
+}
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  let count = match &**id {
+    "re_match" | "re_find_all" | "re_split" => 2,
+    "re_replace" => 3,
+    _ => return None,
+  };
+  if let Some(e) = expect_args(count, param, id) {
+    return Some(e);
+  }
+  let mut args = Vec::new();
+  for (n, p) in param.items.iter().enumerate() {
+    match *p {
+      Evaluation::String(ref s) => args.push(s),
+      _ => return Some(evaluator::exception(ExceptionType::TypeError, id,
+                                            format!("argument {} must be string", n))),
+    }
+  }
+  let re = match Regex::new(args[1]) {
+    Ok(re) => re,
+    Err(e) => {
+      return Some(evaluator::exception(ExceptionType::ParseError, id,
+                                       format!("bad pattern at {}: {}", e.pos, e.msg)));
+    },
+  };
+  let chars: Vec<char> = args[0].chars().collect();
+  let rc = match &**id {
+    "re_match" => {
+      match re.find_at(&chars, 0) {
+        Some(slots) => captures(&chars, &slots),
+        None => Evaluation::List(ListEval { items: Vec::new() }),
+      }
+    },
+    "re_find_all" => {
+      let items = re.find_all(&chars).iter().map(|slots| captures(&chars, slots)).collect();
+      Evaluation::List(ListEval { items })
+    },
+    "re_replace" => {
+      let replacement: Vec<char> = args[2].chars().collect();
+      let mut rc = String::new();
+      let mut last = 0;
+      for slots in re.find_all(&chars) {
+        let (from, to) = (slots[0].unwrap_or(last), slots[1].unwrap_or(last));
+        rc += &text(&chars, last, from);
+        rc += &expand(&replacement, &chars, &slots);
+        last = to;
+      }
+      rc += &text(&chars, last, chars.len());
+      Evaluation::String(rc)
+    },
+    _ => {
+      let mut items = Vec::new();
+      let mut last = 0;
+      for slots in re.find_all(&chars) {
+        let (from, to) = (slots[0].unwrap_or(last), slots[1].unwrap_or(last));
+        // An empty match at either end doesn't split anything off
+        if from == to && (from == 0 || from == chars.len()) {
+          continue;
+        }
+        items.push(Evaluation::String(text(&chars, last, from)));
+        last = to;
+      }
+      items.push(Evaluation::String(text(&chars, last, chars.len())));
+      Evaluation::List(ListEval { items })
+    },
+  };
+  Some(rc)
+}
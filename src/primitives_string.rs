@@ -0,0 +1,152 @@
+// String primitives.  Like strlen and substr, everything here counts
+// characters (Unicode codepoints), never bytes
+
+use std::convert::TryFrom;
+
+use evaluator;
+use primitives::expect_args;
+use primitives::truth;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+
+pub const PRIMITIVES: [&str; 14] = [
+  "split", "join", "find", "contains", "starts_with", "ends_with", "replace",
+  "trim", "upper", "lower", "repeat", "reverse", "char", "codepoint",
+];
+
+fn type_error(id: &String, msg: &str) -> Evaluation {
+  evaluator::exception(ExceptionType::TypeError, id, msg.to_string())
+}
+
+fn runtime_error(id: &String, msg: String) -> Evaluation {
+  evaluator::exception(ExceptionType::RuntimeError, id, msg)
+}
+
+// The first n arguments must all be strings
+fn strings<'a>(n: usize, param: &'a ListEval, id: &String) -> Result<Vec<&'a str>, Evaluation> {
+  let mut rc = Vec::new();
+  for (i, p) in param.items.iter().take(n).enumerate() {
+    match *p {
+      Evaluation::String(ref s) => rc.push(s.as_str()),
+      _ => return Err(type_error(id, &format!("argument {} must be string", i))),
+    }
+  }
+  Ok(rc)
+}
+
+fn list_of_strings(items: Vec<String>) -> Evaluation {
+  Evaluation::List(ListEval { items: items.into_iter().map(Evaluation::String).collect() })
+}
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  let count = match &**id {
+    "trim" | "upper" | "lower" | "reverse" | "char" | "codepoint" => 1,
+    "split" | "join" | "find" | "contains" | "starts_with" | "ends_with" |
+    "repeat" => 2,
+    "replace" => 3,
+    _ => return None,
+  };
+  if let Some(e) = expect_args(count, param, id) {
+    return Some(e);
+  }
+  let rc = match &**id {
+    "join" => {
+      let sep = match param.items[1] {
+        Evaluation::String(ref s) => s,
+        _ => return Some(type_error(id, "argument 1 must be string")),
+      };
+      match param.items[0] {
+        Evaluation::List(ref list) => {
+          let mut parts = Vec::new();
+          for i in &list.items {
+            match *i {
+              Evaluation::String(ref s) => parts.push(s.as_str()),
+              _ => return Some(type_error(id, "argument 0 must be list of strings")),
+            }
+          }
+          Evaluation::String(parts.join(sep))
+        },
+        _ => type_error(id, "argument 0 must be list of strings"),
+      }
+    },
+    "reverse" => {
+      match param.items[0] {
+        Evaluation::String(ref s) => Evaluation::String(s.chars().rev().collect()),
+        Evaluation::List(ref list) => {
+          Evaluation::List(ListEval { items: list.items.iter().rev().map(|i| i.clone()).collect() })
+        },
+        _ => type_error(id, "string or list argument expected"),
+      }
+    },
+    "char" => {
+      match param.items[0] {
+        Evaluation::Integer(n) => {
+          match u32::try_from(n).ok().and_then(char::from_u32) {
+            Some(c) => Evaluation::String(c.to_string()),
+            None => runtime_error(id, format!("{} isn't a valid codepoint", n)),
+          }
+        },
+        _ => type_error(id, "integer argument expected"),
+      }
+    },
+    "repeat" => {
+      let s = match strings(1, param, id) {
+        Ok(s) => s[0],
+        Err(e) => return Some(e),
+      };
+      match param.items[1] {
+        Evaluation::Integer(n) if n < 0 => {
+          runtime_error(id, format!("can't repeat a string {} times", n))
+        },
+        Evaluation::Integer(n) => {
+          // Ask for more than could ever fit and repeat would just panic
+          match usize::try_from(n).ok().and_then(|n| s.len().checked_mul(n)) {
+            Some(size) if size <= isize::MAX as usize => {
+              Evaluation::String(s.repeat(n as usize))
+            },
+            _ => evaluator::exception(ExceptionType::Overflow, id,
+                                      "repeated string too long".to_string()),
+          }
+        },
+        _ => type_error(id, "argument 1 must be integer"),
+      }
+    },
+    _ => {
+      let args = match strings(count, param, id) {
+        Ok(args) => args,
+        Err(e) => return Some(e),
+      };
+      let s = args[0];
+      match &**id {
+        "split" | "replace" if args[1].is_empty() => {
+          runtime_error(id, "separator can't be empty".to_string())
+        },
+        "split" => list_of_strings(s.split(args[1]).map(|p| p.to_string()).collect()),
+        "replace" => Evaluation::String(s.replace(args[1], args[2])),
+        // Index in characters, or -1 if it isn't there
+        "find" => {
+          match s.find(args[1]) {
+            Some(b) => Evaluation::Integer(s[..b].chars().count() as i64),
+            None => Evaluation::Integer(-1),
+          }
+        },
+        "contains" => truth(s.contains(args[1])),
+        "starts_with" => truth(s.starts_with(args[1])),
+        "ends_with" => truth(s.ends_with(args[1])),
+        "trim" => Evaluation::String(s.trim().to_string()),
+        "upper" => Evaluation::String(s.to_uppercase()),
+        "lower" => Evaluation::String(s.to_lowercase()),
+        _ => {
+          let mut chars = s.chars();
+          match (chars.next(), chars.next()) {
+            (Some(c), None) => Evaluation::Integer(i64::from(u32::from(c))),
+            _ => runtime_error(id, "string of exactly one character expected".to_string()),
+          }
+        },
+      }
+    },
+  };
+  Some(rc)
+}
@@ -0,0 +1,45 @@
+// Asking what kind of value something is.  type gives a symbol naming it, and
+// the predicates answer for one kind each (int? takes big integers as well,
+// since they're just as much integers, and bigint? only takes those)
+
+use primitives::expect_args;
+use primitives::truth;
+use symbol::Symbol;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+
+pub const PRIMITIVES: [&str; 9] = [
+  "type", "atom?", "symbol?", "int?", "bigint?", "float?", "string?", "list?",
+  "function?",
+];
+
+fn kind(e: &Evaluation) -> &'static str {
+  match *e {
+    Evaluation::True | Evaluation::False => "atom",
+    Evaluation::Symbol(_) => "symbol",
+    Evaluation::Integer(_) => "int",
+    Evaluation::BigInt(_) => "bigint",
+    Evaluation::Float(_) => "float",
+    Evaluation::String(_) => "string",
+    Evaluation::List(_) => "list",
+    Evaluation::Function(_) => "function",
+    // Never gets this far, since exceptions aren't passed to primitives
+    Evaluation::Exception(_) => "exception",
+  }
+}
+
+pub fn system_functions(id: &String, param: &ListEval) -> Option<Evaluation> {
+  if !PRIMITIVES.contains(&&**id) {
+    return None;
+  }
+  if let Some(e) = expect_args(1, param, id) {
+    return Some(e);
+  }
+  let k = kind(&param.items[0]);
+  Some(match &**id {
+    "type" => Evaluation::Symbol(Symbol::intern(k)),
+    "int?" => truth(k == "int" || k == "bigint"),
+    _ => truth(id.trim_end_matches('?') == k),
+  })
+}
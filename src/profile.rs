@@ -0,0 +1,257 @@
+// Call profiler: counts, wall time and argument list sizes for every function
+// (primitives included), reported when the program finishes.  It can also
+// write folded stacks (one "a;b;c microseconds" line per distinct stack, for
+// flamegraph tools) and a Chrome trace-event file (for chrome://tracing,
+// Perfetto and the like)
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+use json;
+use json::Json;
+
+pub struct Options {
+  pub folded: Option<String>,
+  pub chrome: Option<String>
+}
+
+#[derive(Default)]
+struct Stats {
+  calls: u64,
+  // Time from entering to leaving, only counted for the outermost call when
+  // it's recursive (so it never adds up to more than the run took)
+  inclusive: Duration,
+  // Time not spent in other calls
+  exclusive: Duration,
+  arg_items: u64,
+  max_args: usize
+}
+
+struct Frame {
+  id: String,
+  start: Instant,
+  children: Duration,
+  args: usize
+}
+
+struct Profiler {
+  options: Options,
+  began: Instant,
+  stack: Vec<Frame>,
+  stats: HashMap<String, Stats>,
+  folded: HashMap<String, Duration>,
+  events: Vec<Json>
+}
+
+thread_local! {
+  static ACTIVE: Cell<bool> = const { Cell::new(false) };
+
+  static PROFILER: RefCell<Option<Profiler>> = const { RefCell::new(None) };
+}
+
+pub fn start(options: Options) {
+  PROFILER.with(|p| *p.borrow_mut() = Some(Profiler {
+    options,
+    began: Instant::now(),
+    stack: Vec::new(),
+    stats: HashMap::new(),
+    folded: HashMap::new(),
+    events: Vec::new()
+  }));
+  ACTIVE.with(|a| a.set(true));
+}
+
+pub fn active() -> bool {
+  ACTIVE.with(|a| a.get())
+}
+
+fn micros(d: Duration) -> u64 {
+  d.as_secs() * 1_000_000 + u64::from(d.subsec_micros())
+}
+
+fn millis(d: Duration) -> f64 {
+  d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1_000_000.0
+}
+
+pub fn enter(id: &str, args: usize) {
+  PROFILER.with(|p| {
+    if let Some(ref mut profiler) = *p.borrow_mut() {
+      profiler.stack.push(Frame { id: id.to_string(), start: Instant::now(),
+                                  children: Duration::new(0, 0), args });
+    }
+  });
+}
+
+pub fn leave() {
+  PROFILER.with(|p| {
+    if let Some(ref mut profiler) = *p.borrow_mut() {
+      profiler.leave();
+    }
+  });
+}
+
+impl Profiler {
+  fn leave(&mut self) {
+    let frame = match self.stack.pop() {
+      Some(frame) => frame,
+      None => return,
+    };
+    let elapsed = frame.start.elapsed();
+    let exclusive = elapsed.checked_sub(frame.children).unwrap_or_default();
+    if let Some(parent) = self.stack.last_mut() {
+      parent.children += elapsed;
+    }
+    let recursive = self.stack.iter().any(|f| f.id == frame.id);
+
+    let stats = self.stats.entry(frame.id.clone()).or_default();
+    stats.calls += 1;
+    if !recursive {
+      stats.inclusive += elapsed;
+    }
+    stats.exclusive += exclusive;
+    stats.arg_items += frame.args as u64;
+    stats.max_args = stats.max_args.max(frame.args);
+
+    if self.options.folded.is_some() {
+      let mut path: Vec<&str> = self.stack.iter().map(|f| f.id.as_str()).collect();
+      path.push(&frame.id);
+      *self.folded.entry(path.join(";")).or_default() += exclusive;
+    }
+    if self.options.chrome.is_some() {
+      let start = frame.start.duration_since(self.began);
+      self.events.push(json::object(vec![
+        ("name", json::string(&frame.id)),
+        ("ph", json::string("X")),
+        ("ts", Json::Number(micros(start) as f64)),
+        ("dur", Json::Number(micros(elapsed) as f64)),
+        ("pid", json::number(1)),
+        ("tid", json::number(1)),
+        ("args", json::object(vec![("items", json::number(frame.args))]))]));
+    }
+  }
+
+  fn report(&self, out: &mut dyn Write) -> io::Result<()> {
+    let mut rows: Vec<(&String, &Stats)> = self.stats.iter().collect();
+    rows.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+    writeln!(out, "\n{:>10} {:>12} {:>12} {:>9} {:>8}  function",
+             "calls", "incl ms", "excl ms", "avg args", "max args")?;
+    for (id, s) in rows {
+      writeln!(out, "{:>10} {:>12.3} {:>12.3} {:>9.2} {:>8}  {}",
+               s.calls, millis(s.inclusive), millis(s.exclusive),
+               s.arg_items as f64 / s.calls as f64, s.max_args, id)?;
+    }
+    writeln!(out, "\ntotal {:.3} ms", millis(self.began.elapsed()))
+  }
+
+  fn folded_stacks(&self, out: &mut dyn Write) -> io::Result<()> {
+    let mut stacks: Vec<(&String, &Duration)> = self.folded.iter().collect();
+    stacks.sort();
+    for (stack, time) in stacks {
+      writeln!(out, "{} {}", stack, micros(*time))?;
+    }
+    Ok(())
+  }
+
+  fn chrome_trace(&self, out: &mut dyn Write) -> io::Result<()> {
+    let trace = json::object(vec![("traceEvents", Json::Array(self.events.clone())),
+                                  ("displayTimeUnit", json::string("ms"))]);
+    writeln!(out, "{}", trace)
+  }
+}
+
+fn write_file(filename: &str, write: &dyn Fn(&mut dyn Write) -> io::Result<()>) ->
+  io::Result<()> {
+  let mut out = BufWriter::new(File::create(filename)?);
+  write(&mut out)?;
+  out.flush()
+}
+
+// Stop profiling, print the report (on stderr, out of the program's way) and
+// write out whatever files were asked for
+pub fn stop() {
+  ACTIVE.with(|a| a.set(false));
+  let profiler = match PROFILER.with(|p| p.borrow_mut().take()) {
+    Some(profiler) => profiler,
+    None => return,
+  };
+  if let Err(e) = profiler.report(&mut io::stderr()) {
+    eprintln!("unable to write profile: {}", e);
+  }
+  if let Some(ref filename) = profiler.options.folded {
+    if let Err(e) = write_file(filename, &|out| profiler.folded_stacks(out)) {
+      eprintln!("unable to write folded stacks to {}: {}", filename, e);
+    }
+  }
+  if let Some(ref filename) = profiler.options.chrome {
+    if let Err(e) = write_file(filename, &|out| profiler.chrome_trace(out)) {
+      eprintln!("unable to write trace events to {}: {}", filename, e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // main calls f twice, and the first f calls +
+  fn profile() -> Profiler {
+    start(Options { folded: Some(String::new()), chrome: Some(String::new()) });
+    enter("main", 0);
+    enter("f", 1);
+    enter("+", 2);
+    leave();
+    leave();
+    enter("f", 1);
+    leave();
+    leave();
+    ACTIVE.with(|a| a.set(false));
+    PROFILER.with(|p| p.borrow_mut().take()).unwrap()
+  }
+
+  #[test]
+  fn stats() {
+    let profiler = profile();
+    assert_eq!(profiler.stats["f"].calls, 2);
+    assert_eq!(profiler.stats["f"].arg_items, 2);
+    assert_eq!(profiler.stats["+"].max_args, 2);
+    assert!(profiler.stats["main"].inclusive >= profiler.stats["f"].inclusive);
+  }
+
+  #[test]
+  fn folded_stacks() {
+    let mut out = Vec::new();
+    profile().folded_stacks(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let stacks: Vec<&str> = text.lines().map(|l| {
+      let (stack, time) = l.rsplit_once(' ').unwrap();
+      assert!(time.parse::<u64>().is_ok(), "bad time in {}", l);
+      stack
+    }).collect();
+    // Both calls to f end up on the same line
+    assert_eq!(stacks, vec!["main", "main;f", "main;f;+"]);
+  }
+
+  #[test]
+  fn chrome_trace() {
+    let mut out = Vec::new();
+    profile().chrome_trace(&mut out).unwrap();
+    let trace = json::parse(&String::from_utf8(out).unwrap()).unwrap();
+    assert_eq!(trace.get("displayTimeUnit").as_str(), Some("ms"));
+    let events = trace.get("traceEvents").as_array();
+    // In the order they finished
+    let names: Vec<&str> = events.iter().filter_map(|e| e.get("name").as_str()).collect();
+    assert_eq!(names, vec!["+", "f", "f", "main"]);
+    for e in events {
+      assert_eq!(e.get("ph").as_str(), Some("X"));
+      assert!(e.get("ts").as_usize().is_some() && e.get("dur").as_usize().is_some());
+    }
+    assert_eq!(events[0].get("args").get("items"), &json::number(2));
+  }
+}
@@ -0,0 +1,584 @@
+// Regular expressions, run on a Pike VM (a Thompson NFA simulation that keeps
+// track of captures), so matching takes time linear in the length of the
+// text for any given pattern: there's no backtracking for a pattern to make
+// explode, and so no backreferences either.  Positions are in characters.
+//
+// Supported: literals, ., [...] and [^...] classes with ranges, \d \w \s and
+// their negations \D \W \S, escapes (\n \t \r and any punctuation), ^ and $
+// (start and end of text), \b and \B, groups ( ) and (?: ), alternation |,
+// and the repetitions * + ? {n} {n,} {n,m}, each lazy with a trailing ?
+
+// Keeps compiled programs (and the recursion following them) to a sane size
+const MAX_PROGRAM: usize = 10000;
+const MAX_REPEAT: u32 = 1000;
+// Likewise the recursion parsing them, with groups (or repetitions) inside
+// each other
+const MAX_DEPTH: usize = 250;
+
+pub struct PatternError {
+  // Character offset in the pattern
+  pub pos: usize,
+  pub msg: String
+}
+
+#[derive(Clone, Copy)]
+enum Perl {
+  Digit,
+  Word,
+  Space
+}
+
+#[derive(Clone)]
+enum ClassItem {
+  Range(char, char),
+  Perl(Perl, bool)
+}
+
+#[derive(Clone)]
+struct Class {
+  items: Vec<ClassItem>,
+  negated: bool
+}
+
+#[derive(Clone, Copy)]
+enum Assertion {
+  Start,
+  End,
+  WordBoundary,
+  NotWordBoundary
+}
+
+#[derive(Clone)]
+enum Node {
+  Empty,
+  Char(char),
+  Any,
+  Class(Class),
+  Assert(Assertion),
+  // Where each of these two starts in the pattern comes last
+  Group(Box<Node>, Option<usize>, usize),
+  Concat(Vec<Node>),
+  Alternate(Vec<Node>),
+  Repeat(Box<Node>, u32, Option<u32>, bool, usize)
+}
+
+enum Inst {
+  Char(char),
+  Any,
+  Class(Class),
+  Assert(Assertion),
+  // Try the first, then the second
+  Split(usize, usize),
+  Jump(usize),
+  Save(usize),
+  Match
+}
+
+pub struct Regex {
+  program: Vec<Inst>,
+  // Including group 0, the whole match
+  groups: usize
+}
+
+fn perl_matches(kind: Perl, c: char) -> bool {
+  match kind {
+    Perl::Digit => c.is_ascii_digit(),
+    Perl::Word => c.is_alphanumeric() || c == '_',
+    Perl::Space => c.is_whitespace(),
+  }
+}
+
+impl Class {
+  fn matches(&self, c: char) -> bool {
+    let found = self.items.iter().any(|i| match *i {
+      ClassItem::Range(from, to) => from <= c && c <= to,
+      ClassItem::Perl(kind, negated) => perl_matches(kind, c) != negated,
+    });
+    found != self.negated
+  }
+}
+
+fn is_word(c: Option<&char>) -> bool {
+  match c {
+    Some(&c) => perl_matches(Perl::Word, c),
+    None => false,
+  }
+}
+
+struct Parser {
+  chars: Vec<char>,
+  pos: usize,
+  groups: usize,
+  depth: usize
+}
+
+impl Parser {
+  fn error<T>(&self, pos: usize, msg: &str) -> Result<T, PatternError> {
+    Err(PatternError { pos, msg: msg.to_string() })
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).cloned()
+  }
+
+  fn eat(&mut self, c: char) -> bool {
+    if self.peek() == Some(c) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn alternation(&mut self) -> Result<Node, PatternError> {
+    let mut branches = vec![self.concatenation()?];
+    while self.eat('|') {
+      branches.push(self.concatenation()?);
+    }
+    Ok(if branches.len() == 1 { branches.pop().unwrap_or(Node::Empty) } else { Node::Alternate(branches) })
+  }
+
+  fn concatenation(&mut self) -> Result<Node, PatternError> {
+    let mut items = Vec::new();
+    while let Some(c) = self.peek() {
+      if c == '|' || c == ')' {
+        break;
+      }
+      let atom = self.atom()?;
+      items.push(self.repetition(atom)?);
+    }
+    Ok(match items.len() {
+      0 => Node::Empty,
+      1 => items.pop().unwrap_or(Node::Empty),
+      _ => Node::Concat(items),
+    })
+  }
+
+  fn number(&mut self) -> Option<u32> {
+    let start = self.pos;
+    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      self.pos += 1;
+    }
+    let digits: String = self.chars[start..self.pos].iter().collect();
+    digits.parse::<u32>().ok()
+  }
+
+  fn repetition(&mut self, atom: Node) -> Result<Node, PatternError> {
+    let mut node = atom;
+    let mut depth = self.depth;
+    loop {
+      let start = self.pos;
+      let (min, max) = match self.peek() {
+        Some('*') => (0, None),
+        Some('+') => (1, None),
+        Some('?') => (0, Some(1)),
+        Some('{') => {
+          self.pos += 1;
+          let min = match self.number() {
+            Some(n) => n,
+            None => return self.error(start, "bad repetition"),
+          };
+          let max = if self.eat(',') {
+            if self.peek() == Some('}') { None } else { self.number() }
+          } else {
+            Some(min)
+          };
+          if self.peek() != Some('}') {
+            return self.error(start, "bad repetition");
+          }
+          if max.is_some_and(|m| m < min) || min.max(max.unwrap_or(0)) > MAX_REPEAT {
+            return self.error(start, "bad repetition count");
+          }
+          (min, max)
+        },
+        _ => return Ok(node),
+      };
+      self.pos += 1;
+      match node {
+        Node::Empty | Node::Assert(_) => return self.error(start, "nothing to repeat"),
+        _ => {},
+      }
+      let greedy = !self.eat('?');
+      node = Node::Repeat(Box::new(node), min, max, greedy, start);
+      // Each repetition of a repetition wraps it another level deep
+      depth += 1;
+      if depth > MAX_DEPTH {
+        return self.error(start, "pattern nested too deeply");
+      }
+    }
+  }
+
+  fn atom(&mut self) -> Result<Node, PatternError> {
+    let start = self.pos;
+    let c = match self.peek() {
+      Some(c) => c,
+      None => return self.error(start, "unexpected end of pattern"),
+    };
+    self.pos += 1;
+    match c {
+      '.' => Ok(Node::Any),
+      '^' => Ok(Node::Assert(Assertion::Start)),
+      '$' => Ok(Node::Assert(Assertion::End)),
+      '[' => self.class(start),
+      '(' => {
+        let index = if self.eat('?') {
+          if !self.eat(':') {
+            return self.error(start, "unknown group type");
+          }
+          None
+        } else {
+          self.groups += 1;
+          Some(self.groups)
+        };
+        if self.depth == MAX_DEPTH {
+          return self.error(start, "pattern nested too deeply");
+        }
+        self.depth += 1;
+        let inner = self.alternation()?;
+        self.depth -= 1;
+        if !self.eat(')') {
+          return self.error(start, "unclosed group");
+        }
+        Ok(Node::Group(Box::new(inner), index, start))
+      },
+      '*' | '+' | '?' | '{' => self.error(start, "nothing to repeat"),
+      '\\' if self.eat('b') => Ok(Node::Assert(Assertion::WordBoundary)),
+      '\\' if self.eat('B') => Ok(Node::Assert(Assertion::NotWordBoundary)),
+      '\\' => {
+        match self.escape(start)? {
+          ClassItem::Range(c, _) => Ok(Node::Char(c)),
+          perl => Ok(Node::Class(Class { items: vec![perl], negated: false })),
+        }
+      },
+      _ => Ok(Node::Char(c)),
+    }
+  }
+
+  // After a backslash
+  fn escape(&mut self, start: usize) -> Result<ClassItem, PatternError> {
+    let c = match self.peek() {
+      Some(c) => c,
+      None => return self.error(start, "trailing backslash"),
+    };
+    self.pos += 1;
+    Ok(match c {
+      'd' => ClassItem::Perl(Perl::Digit, false),
+      'D' => ClassItem::Perl(Perl::Digit, true),
+      'w' => ClassItem::Perl(Perl::Word, false),
+      'W' => ClassItem::Perl(Perl::Word, true),
+      's' => ClassItem::Perl(Perl::Space, false),
+      'S' => ClassItem::Perl(Perl::Space, true),
+      'n' => ClassItem::Range('\n', '\n'),
+      't' => ClassItem::Range('\t', '\t'),
+      'r' => ClassItem::Range('\r', '\r'),
+      c if c.is_ascii_punctuation() || c == ' ' => ClassItem::Range(c, c),
+      _ => return self.error(start, &format!("unknown escape \\{}", c)),
+    })
+  }
+
+  fn class(&mut self, start: usize) -> Result<Node, PatternError> {
+    let negated = self.eat('^');
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+      let at = self.pos;
+      let c = match self.peek() {
+        Some(c) => c,
+        None => return self.error(start, "unclosed class"),
+      };
+      self.pos += 1;
+      if c == ']' && !first {
+        break;
+      }
+      first = false;
+      let item = if c == '\\' { self.escape(at)? } else { ClassItem::Range(c, c) };
+      let from = match item {
+        ClassItem::Range(from, _) => from,
+        perl => {
+          items.push(perl);
+          continue;
+        },
+      };
+      // A range, unless the - is last (and so literal)
+      if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+        self.pos += 1;
+        let to_at = self.pos;
+        let to = match self.peek() {
+          Some('\\') => {
+            self.pos += 1;
+            match self.escape(to_at)? {
+              ClassItem::Range(to, _) => to,
+              _ => return self.error(to_at, "bad class range"),
+            }
+          },
+          Some(to) => {
+            self.pos += 1;
+            to
+          },
+          None => return self.error(start, "unclosed class"),
+        };
+        if to < from {
+          return self.error(at, "bad class range");
+        }
+        items.push(ClassItem::Range(from, to));
+      } else {
+        items.push(item);
+      }
+    }
+    Ok(Node::Class(Class { items, negated }))
+  }
+}
+
+struct Compiler {
+  program: Vec<Inst>,
+  // The outermost group or repetition being compiled, which gets the blame
+  // if the program gets too big (with neither, it's the whole pattern's
+  // fault, so the blame goes at the start)
+  outermost: Option<usize>
+}
+
+impl Compiler {
+  fn emit(&mut self, inst: Inst) -> Result<usize, PatternError> {
+    if self.program.len() >= MAX_PROGRAM {
+      let pos = self.outermost.unwrap_or(0);
+      return Err(PatternError { pos, msg: "pattern too big".to_string() });
+    }
+    self.program.push(inst);
+    Ok(self.program.len() - 1)
+  }
+
+  fn patch(&mut self, at: usize, first: usize, second: usize) {
+    self.program[at] = Inst::Split(first, second);
+  }
+
+  // A split preferring the next instruction if greedy, else the target
+  fn split(&mut self, at: usize, target: usize, greedy: bool) {
+    if greedy {
+      self.patch(at, at + 1, target);
+    } else {
+      self.patch(at, target, at + 1);
+    }
+  }
+
+  fn compile(&mut self, node: &Node) -> Result<(), PatternError> {
+    let outermost = self.outermost.is_none();
+    if let Node::Group(_, _, pos) | Node::Repeat(_, _, _, _, pos) = *node {
+      if outermost {
+        self.outermost = Some(pos);
+      }
+    }
+    self.expand(node)?;
+    if outermost {
+      self.outermost = None;
+    }
+    Ok(())
+  }
+
+  fn expand(&mut self, node: &Node) -> Result<(), PatternError> {
+    match *node {
+      Node::Empty => {},
+      Node::Char(c) => { self.emit(Inst::Char(c))?; },
+      Node::Any => { self.emit(Inst::Any)?; },
+      Node::Class(ref class) => { self.emit(Inst::Class(class.clone()))?; },
+      Node::Assert(a) => { self.emit(Inst::Assert(a))?; },
+      Node::Group(ref inner, index, _) => {
+        if let Some(n) = index {
+          self.emit(Inst::Save(n * 2))?;
+        }
+        self.compile(inner)?;
+        if let Some(n) = index {
+          self.emit(Inst::Save(n * 2 + 1))?;
+        }
+      },
+      Node::Concat(ref items) => {
+        for i in items {
+          self.compile(i)?;
+        }
+      },
+      Node::Alternate(ref branches) => {
+        let mut jumps = Vec::new();
+        for (n, b) in branches.iter().enumerate() {
+          if n + 1 < branches.len() {
+            let split = self.emit(Inst::Split(0, 0))?;
+            self.compile(b)?;
+            jumps.push(self.emit(Inst::Jump(0))?);
+            let next = self.program.len();
+            self.patch(split, split + 1, next);
+          } else {
+            self.compile(b)?;
+          }
+        }
+        let end = self.program.len();
+        for j in jumps {
+          self.program[j] = Inst::Jump(end);
+        }
+      },
+      Node::Repeat(ref inner, min, max, greedy, _) => {
+        for _ in 0..min {
+          self.compile(inner)?;
+        }
+        match max {
+          None => {
+            // L: split L+1, out; inner; jump L
+            let split = self.emit(Inst::Split(0, 0))?;
+            self.compile(inner)?;
+            self.emit(Inst::Jump(split))?;
+            let end = self.program.len();
+            self.split(split, end, greedy);
+          },
+          Some(max) => {
+            // Each optional copy can skip straight to the end
+            let mut splits = Vec::new();
+            for _ in min..max {
+              splits.push(self.emit(Inst::Split(0, 0))?);
+              self.compile(inner)?;
+            }
+            let end = self.program.len();
+            for s in splits {
+              self.split(s, end, greedy);
+            }
+          },
+        }
+      },
+    }
+    Ok(())
+  }
+}
+
+// Threads waiting at each instruction for the current position, in priority
+// order, and which instructions already have one
+struct Threads {
+  list: Vec<(usize, Vec<Option<usize>>)>,
+  seen: Vec<bool>
+}
+
+impl Threads {
+  fn new(size: usize) -> Threads {
+    Threads { list: Vec::new(), seen: vec![false; size] }
+  }
+
+  fn clear(&mut self) {
+    self.list.clear();
+    for s in self.seen.iter_mut() {
+      *s = false;
+    }
+  }
+}
+
+impl Regex {
+  pub fn new(pattern: &str) -> Result<Regex, PatternError> {
+    let mut parser = Parser { chars: pattern.chars().collect(), pos: 0, groups: 0,
+                             depth: 0 };
+    let node = parser.alternation()?;
+    if parser.pos < parser.chars.len() {
+      // The only thing that stops the top level early
+      return parser.error(parser.pos, "unmatched )");
+    }
+    let mut compiler = Compiler { program: Vec::new(), outermost: None };
+    // The whole match is group 0, but it's no use blaming that for anything
+    compiler.emit(Inst::Save(0))?;
+    compiler.compile(&node)?;
+    compiler.emit(Inst::Save(1))?;
+    compiler.emit(Inst::Match)?;
+    Ok(Regex { program: compiler.program, groups: parser.groups + 1 })
+  }
+
+  pub fn groups(&self) -> usize {
+    self.groups
+  }
+
+  // Follow everything that doesn't consume a character from pc, adding the
+  // threads that do (or match) to the list
+  fn add(&self, threads: &mut Threads, pc: usize, mut slots: Vec<Option<usize>>,
+         text: &[char], pos: usize) {
+    if threads.seen[pc] {
+      return;
+    }
+    threads.seen[pc] = true;
+    match self.program[pc] {
+      Inst::Jump(to) => self.add(threads, to, slots, text, pos),
+      Inst::Split(first, second) => {
+        self.add(threads, first, slots.clone(), text, pos);
+        self.add(threads, second, slots, text, pos);
+      },
+      Inst::Save(n) => {
+        slots[n] = Some(pos);
+        self.add(threads, pc + 1, slots, text, pos);
+      },
+      Inst::Assert(a) => {
+        let before = if pos > 0 { text.get(pos - 1) } else { None };
+        let holds = match a {
+          Assertion::Start => pos == 0,
+          Assertion::End => pos == text.len(),
+          Assertion::WordBoundary => is_word(before) != is_word(text.get(pos)),
+          Assertion::NotWordBoundary => is_word(before) == is_word(text.get(pos)),
+        };
+        if holds {
+          self.add(threads, pc + 1, slots, text, pos);
+        }
+      },
+      _ => threads.list.push((pc, slots)),
+    }
+  }
+
+  // Leftmost match starting at or after start: the start and end of each
+  // group (None for groups that didn't take part)
+  pub fn find_at(&self, text: &[char], start: usize) -> Option<Vec<Option<usize>>> {
+    let mut current = Threads::new(self.program.len());
+    let mut next = Threads::new(self.program.len());
+    let mut matched = None;
+    let mut pos = start;
+    loop {
+      // Until something matches, a new attempt can start here (behind
+      // everything already running, which started further left)
+      if matched.is_none() {
+        self.add(&mut current, 0, vec![None; self.groups * 2], text, pos);
+      }
+      if current.list.is_empty() && matched.is_some() {
+        break;
+      }
+      let c = text.get(pos);
+      let list = std::mem::take(&mut current.list);
+      for (pc, slots) in list {
+        let step = match (&self.program[pc], c) {
+          (Inst::Match, _) => {
+            matched = Some(slots);
+            // Everything after this thread is lower priority
+            break;
+          },
+          (Inst::Char(want), Some(&c)) => *want == c,
+          (Inst::Any, Some(&c)) => c != '\n',
+          (Inst::Class(class), Some(&c)) => class.matches(c),
+          _ => false,
+        };
+        if step {
+          self.add(&mut next, pc + 1, slots, text, pos + 1);
+        }
+      }
+      if pos >= text.len() {
+        break;
+      }
+      std::mem::swap(&mut current, &mut next);
+      next.clear();
+      pos += 1;
+    }
+    matched
+  }
+
+  // Every non-overlapping match, left to right; after an empty match, the
+  // next one starts a character further on
+  pub fn find_all(&self, text: &[char]) -> Vec<Vec<Option<usize>>> {
+    let mut rc = Vec::new();
+    let mut start = 0;
+    while start <= text.len() {
+      let slots = match self.find_at(text, start) {
+        Some(slots) => slots,
+        None => break,
+      };
+      let (from, to) = (slots[0].unwrap_or(start), slots[1].unwrap_or(start));
+      start = if to == from { to + 1 } else { to };
+      rc.push(slots);
+    }
+    rc
+  }
+}
@@ -0,0 +1,292 @@
+// Interactive read-eval-print loop.  Everything entered is evaluated in one
+// persistent main scope, so definitions stick around between inputs.  Unlike
+// in a program, defining a name again at the top level replaces it, so a
+// mistake can be fixed without starting over
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+use host;
+use parser;
+use tokenizer;
+
+use encoding::Block;
+use encoding::Expression;
+use encoding::Scope;
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::ExceptionType;
+use encoding::ParseError;
+
+const HELP: &str = "  :load <file>   evaluate a source file into the current scope
+  :defs          list definitions in the current scope
+  :reset         forget all definitions
+  :history       list previous inputs
+  :redo <n>      evaluate previous input <n> again
+  :help          show this message
+  :quit          exit (so does end of input)
+Defining a name that's already defined replaces the old definition";
+
+fn new_scope() -> Vec<Scope> {
+  vec![Scope { bindings: HashMap::new(), param: ListEval { items: Vec::new() } }]
+}
+
+fn prompt(continuing: bool) {
+  if continuing {
+    print!("   ...> ");
+  } else {
+    print!("curry> ");
+  }
+  let _ = io::stdout().flush();
+}
+
+// Parse errors at EOF just mean we haven't seen the whole thing yet (unclosed
+// list or string, or an expression still waiting for its semicolon)
+fn is_incomplete(e: &ParseError) -> bool {
+  e.lnum == -1
+}
+
+fn parse(source: &str) -> Result<Block, ParseError> {
+  let tokens = tokenizer::tokenize(source)?;
+  parser::parse(&tokens)
+}
+
+// Like Block::evaluate for the main program, except the scope isn't popped
+// afterwards, and we print what we get instead of just throwing it away
+fn evaluate(block: &Block, scope: &mut Vec<Scope>, show: bool) {
+  let mut value = None;
+  for e in &block.expressions {
+    if let Expression::Definition(ref def) = *e {
+      scope[0].bindings.remove(&def.id);
+    }
+    match e.evaluate(scope) {
+      Evaluation::Exception(ref ex) => {
+        match ex.flavor {
+          ExceptionType::Return => {
+            value = Some(ex.payload.clone());
+            break;
+          },
+          _ => {
+            let mut rc = ex.clone();
+            rc.stack.push("[repl]".to_string());
+            host::write_line(&rc.to_string());
+            return;
+          },
+        }
+      },
+      ev => { value = Some(ev) },
+    }
+  }
+  if show {
+    if let Some(v) = value {
+      host::write_line(&v.to_string());
+    }
+  }
+}
+
+fn load(filename: &str, scope: &mut Vec<Scope>) {
+  let mut source = String::new();
+  match File::open(filename).and_then(|mut f| f.read_to_string(&mut source)) {
+    Ok(_) => {
+      match parse(&source) {
+        Ok(block) => evaluate(&block, scope, false),
+        Err(e) => host::write_line(&e.to_string()),
+      }
+    },
+    Err(e) => host::write_line(&format!("unable to load {}: {}", filename, e)),
+  }
+}
+
+// Meta-commands only count at the start of an entry, and never look like code
+// (an anonymous definition will always have a semicolon somewhere)
+fn meta_command(line: &str) -> Option<(&str, &str)> {
+  let line = line.trim();
+  if !line.starts_with(':') || line.contains(';') || line.contains('[') {
+    return None;
+  }
+  let mut parts = line[1..].splitn(2, char::is_whitespace);
+  let command = parts.next().unwrap_or("");
+  let arg = parts.next().unwrap_or("").trim();
+  match command {
+    "load" | "defs" | "reset" | "history" | "redo" | "help" | "quit" =>
+      Some((command, arg)),
+    _ => None,
+  }
+}
+
+pub fn run() {
+  let mut scope = new_scope();
+  let mut history: Vec<String> = Vec::new();
+  let mut buffer = String::new();
+
+  host::write_line("curry interactive mode; :help for commands");
+  loop {
+    prompt(!buffer.is_empty());
+    let line = match host::read_line() {
+      Ok(Some(line)) => line,
+      Ok(None) => {
+        host::write_line("");
+        break;
+      },
+      Err(e) => {
+        host::write_line(&format!("unable to read input: {}", e));
+        break;
+      },
+    };
+
+    let mut entry = None;
+    if buffer.is_empty() {
+      if line.trim().is_empty() {
+        continue;
+      }
+      if let Some((command, arg)) = meta_command(&line) {
+        match command {
+          "load" => load(arg, &mut scope),
+          "defs" => {
+            let mut names: Vec<&String> = scope[0].bindings.keys().collect();
+            names.sort();
+            for name in names {
+              host::write_line(name);
+            }
+          },
+          "reset" => scope = new_scope(),
+          "history" => {
+            for (n, input) in history.iter().enumerate() {
+              host::write_line(&format!("{:4}  {}", n + 1, input.replace('\n', "\n      ")));
+            }
+          },
+          "redo" => {
+            match arg.parse::<usize>() {
+              Ok(n) if n >= 1 && n <= history.len() => {
+                entry = Some(history[n - 1].clone());
+              },
+              _ => host::write_line(&format!("no such history entry: {}", arg)),
+            }
+          },
+          "help" => host::write_line(HELP),
+          _ => break,
+        }
+        if entry.is_none() {
+          continue;
+        }
+      }
+    }
+
+    let source = match entry {
+      Some(source) => source,
+      None => {
+        buffer += &line;
+        buffer += "\n";
+        match parse(&buffer) {
+          Err(ref e) if is_incomplete(e) => continue,
+          _ => {},
+        }
+        let source = buffer.trim_end().to_string();
+        buffer.clear();
+        source
+      },
+    };
+    history.push(source.clone());
+    match parse(&(source + "\n")) {
+      Ok(block) => evaluate(&block, &mut scope, true),
+      Err(e) => host::write_line(&e.to_string()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+  use std::fs;
+  use std::io::Cursor;
+  use std::process;
+
+  // Everything the REPL prints for this input, minus the greeting
+  fn session(input: &str) -> String {
+    host::set_input(Box::new(Cursor::new(input.to_string())));
+    host::capture_output();
+    run();
+    let output = host::take_output();
+    output.trim_start_matches("curry interactive mode; :help for commands\n").to_string()
+  }
+
+  #[test]
+  fn incomplete_input() {
+    for source in &["+[1\n", "\"abc\n", "+[1 2]\n", "f:+[1 2];\n"] {
+      assert!(parse(source).map_err(|e| is_incomplete(&e)).err() == Some(true), "{:?}", source);
+    }
+    // Complete, or wrong in a way more input won't fix
+    assert!(parse("+[1 2];\n").is_ok());
+    assert!(parse("];\n").map_err(|e| is_incomplete(&e)).err() == Some(false));
+  }
+
+  #[test]
+  fn multiple_lines() {
+    assert_eq!(session("+[1\n2];\n\"a\nb\";\n"), "3\n\"a\nb\"\n\n");
+    // A parse error throws the entry away, rather than waiting for more
+    assert_eq!(session("];\n1;\n"),
+               "--- Parse error on line 1 :\n--- syntax error, unexpected token :\n\"];\"\n1\n\n");
+  }
+
+  #[test]
+  fn persistent_scope() {
+    assert_eq!(session("inc:+[car[_] 1];;\ninc[1];\ninc[inc[1]];\n"), ":<...>\n2\n3\n\n");
+  }
+
+  #[test]
+  fn redefinition() {
+    assert_eq!(session("f:1;;\nf:2;;\nf[];\n"), ":<...>\n:<...>\n2\n\n");
+    // Only at the top level; a function still can't redefine its own names
+    assert!(session("g:f:1;;f:2;;;\ng[];\n").contains("REDEFINITION ERROR"));
+  }
+
+  #[test]
+  fn defs_and_reset() {
+    assert_eq!(session("b:1;;\na:2;;\n:defs\n:reset\n:defs\n"), ":<...>\n:<...>\na\nb\n\n");
+    assert!(session("a:2;;\n:reset\na[];\n").contains("a : function is not defined in scope"));
+  }
+
+  #[test]
+  fn history_and_redo() {
+    assert_eq!(session("1;\n+[1\n1];\n:history\n:redo 2\n:redo 9\n:history\n"), "\
+1
+2
+   1  1;
+   2  +[1
+      1];
+2
+no such history entry: 9
+   1  1;
+   2  +[1
+      1];
+   3  +[1
+      1];
+
+");
+  }
+
+  #[test]
+  fn load() {
+    let path = env::temp_dir().join(format!("curry-repl-{}.cry", process::id()));
+    fs::write(&path, "double:+[car[_] car[_]];;\n").unwrap();
+    let name = path.to_str().unwrap();
+    // Loading doesn't print anything, and loading again replaces what it defined
+    assert_eq!(session(&format!(":load {}\n:load {}\ndouble[2];\n", name, name)), "4\n\n");
+    fs::remove_file(&path).unwrap();
+    assert!(session(&format!(":load {}\n", name)).starts_with(&format!("unable to load {}", name)));
+  }
+
+  #[test]
+  fn meta_commands() {
+    assert!(session(":help\n").starts_with("  :load <file>"));
+    // Nothing after :quit gets run
+    assert_eq!(session(":quit\n1;\n"), "");
+    // Things that only look like meta-commands are code
+    assert!(meta_command(":car[_];").is_none());
+    assert!(meta_command(":nope").is_none());
+    assert_eq!(meta_command("  :redo 3 "), Some(("redo", "3")));
+  }
+}
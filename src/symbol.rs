@@ -0,0 +1,45 @@
+// Symbols: names that stand for themselves, like 'ok or 'not_found.  Each
+// distinct name is interned the first time it turns up, so a symbol is just
+// its number in the table and comparing two of them is comparing numbers
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(usize);
+
+struct Table {
+  names: Vec<String>,
+  numbers: HashMap<String, usize>
+}
+
+thread_local! {
+  static TABLE: RefCell<Table> =
+    RefCell::new(Table { names: Vec::new(), numbers: HashMap::new() });
+}
+
+// Anything that reads back as the same symbol: the ' and then at least one
+// character that could be in an id
+pub fn valid_name(name: &str) -> bool {
+  let reserved = [':', ';', '[', ']', '"', '#'];
+  !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || reserved.contains(&c))
+}
+
+impl Symbol {
+  pub fn intern(name: &str) -> Symbol {
+    TABLE.with(|t| {
+      let mut table = t.borrow_mut();
+      if let Some(&n) = table.numbers.get(name) {
+        return Symbol(n);
+      }
+      let n = table.names.len();
+      table.names.push(name.to_string());
+      table.numbers.insert(name.to_string(), n);
+      Symbol(n)
+    })
+  }
+
+  pub fn name(&self) -> String {
+    TABLE.with(|t| t.borrow().names[self.0].clone())
+  }
+}
@@ -0,0 +1,163 @@
+// Built-in test runner.  A test is any top-level definition marked with a
+// #[test] comment on the line(s) above it, e.g.:
+//
+//   #[test]
+//   addition_works:?[=[+[1 1] 2] true raise["nope"]];;
+//
+// Each test is run on its own, with only the file's top-level definitions in
+// scope (none of the other top-level expressions are evaluated), and fails if
+// it raises an exception
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use coverage;
+use host;
+use parser;
+use tokenizer;
+
+use encoding::Block;
+use encoding::Expression;
+use encoding::Scope;
+use encoding::Evaluation;
+use encoding::ListEval;
+use encoding::Exception;
+
+pub struct Summary {
+  pub passed: usize,
+  pub failed: usize
+}
+
+// Line numbers are 1-based, and anything between the marker and the
+// definition has to be blank or another comment
+pub fn is_marked(lines: &[&str], lnum: isize) -> bool {
+  let mut n = lnum - 2;
+  while n >= 0 && (n as usize) < lines.len() {
+    let line = lines[n as usize].trim();
+    if line == "#[test]" {
+      return true;
+    }
+    if !line.is_empty() && !line.starts_with('#') {
+      return false;
+    }
+    n -= 1;
+  }
+  false
+}
+
+fn find_tests(block: &Block, source: &str, filter: Option<&str>) ->
+  Vec<String> {
+  let lines: Vec<&str> = source.lines().collect();
+  let mut rc = Vec::new();
+  for e in &block.expressions {
+    if let Expression::Definition(ref def) = *e {
+      if def.id.is_empty() || !is_marked(&lines, def.lnum) {
+        continue;
+      }
+      match filter {
+        Some(pattern) if !def.id.contains(pattern) => {},
+        _ => rc.push(def.id.clone()),
+      }
+    }
+  }
+  rc
+}
+
+// Set up a fresh main scope with just the top-level definitions, then call
+// the test in it
+fn run_test(block: &Block, name: &str) -> Result<(), Exception> {
+  let empty = ListEval { items: Vec::new() };
+  let mut scope = vec![Scope { bindings: HashMap::new(), param: empty.clone() }];
+  for e in &block.expressions {
+    if let Expression::Definition(ref def) = *e {
+      if let Evaluation::Exception(ex) = def.evaluate(&mut scope) {
+        return Err(ex);
+      }
+    }
+  }
+  let func = scope[0].bindings[name].clone();
+  if coverage::active() {
+    coverage::function_hit(func.lnum, func.col);
+  }
+  match func.block.evaluate(&mut scope, &empty, &name.to_string()) {
+    Evaluation::Exception(ex) => Err(ex),
+    _ => Ok(()),
+  }
+}
+
+fn run_file(filename: &str, filter: Option<&str>, summary: &mut Summary) {
+  let mut source = String::new();
+  if let Err(e) = File::open(filename)
+    .and_then(|mut f| f.read_to_string(&mut source)) {
+    println!("- : {} : unable to read file: {}", filename, e);
+    summary.failed += 1;
+    return;
+  }
+  if !source.ends_with('\n') {
+    source.push('\n');
+  }
+  let parsed = tokenizer::tokenize(&source)
+    .and_then(|tokens| parser::parse(&tokens));
+  let block = match parsed {
+    Ok(block) => block,
+    Err(e) => {
+      println!("- : {} : unable to parse file:\n{}", filename, e);
+      summary.failed += 1;
+      return;
+    },
+  };
+
+  host::set_script_path(Some(filename.to_string()));
+  if coverage::active() {
+    coverage::add_file(filename, &source, &block);
+  }
+  for name in find_tests(&block, &source, filter) {
+    match run_test(&block, &name) {
+      Ok(_) => {
+        println!("+ : {} : {}", filename, name);
+        summary.passed += 1;
+      },
+      Err(ex) => {
+        println!("- : {} : {}{}", filename, name, ex);
+        summary.failed += 1;
+      },
+    }
+  }
+}
+
+// Source files named directly, plus every .cry file under any directories
+pub fn collect_files(path: &Path, files: &mut Vec<String>) {
+  if path.is_dir() {
+    let mut entries = Vec::new();
+    if let Ok(dir) = fs::read_dir(path) {
+      for entry in dir.flatten() {
+        entries.push(entry.path());
+      }
+    }
+    entries.sort();
+    for entry in entries {
+      if entry.is_dir() ||
+        entry.extension().map(|x| x == "cry").unwrap_or(false) {
+        collect_files(&entry, files);
+      }
+    }
+  } else {
+    files.push(path.to_string_lossy().into_owned());
+  }
+}
+
+pub fn run(paths: &[String], filter: Option<&str>) -> Summary {
+  let mut files = Vec::new();
+  for path in paths {
+    collect_files(Path::new(path), &mut files);
+  }
+  let mut summary = Summary { passed: 0, failed: 0 };
+  for f in &files {
+    run_file(f, filter, &mut summary);
+  }
+  println!("\n{} passed, {} failed", summary.passed, summary.failed);
+  summary
+}
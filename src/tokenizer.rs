@@ -3,6 +3,10 @@
 use encoding::LineLookup;
 use encoding::Token;
 use encoding::TokenValue;
+use encoding::ParseError;
+
+use bigint::BigInt;
+use symbol::Symbol;
 
 // TODO: this is for one call to parse_error; set up a more general utility?
 use parser;
@@ -15,8 +19,12 @@ fn get_line(pos: usize, key: &LineLookup) -> String {
   key.lines[key.lnums[pos]].clone()
 }
 
+fn get_col(pos: usize, key: &LineLookup) -> usize {
+  pos - key.starts[key.lnums[pos]]
+}
+
 fn next_token(chars: &Vec<char>, start: usize, key: &LineLookup) ->
-  (Token, usize) {
+  Result<(Token, usize), ParseError> {
   let reserved = [':', ';', '[', ']', '"', '#'];
 
   let mut index = start;
@@ -25,8 +33,8 @@ fn next_token(chars: &Vec<char>, start: usize, key: &LineLookup) ->
     if index == chars.len() - 1 {
       // EOF is only returned with trailing whitespace (or closing comment), but
       // we need to return something when there's no "real" token left to return
-      return (Token { value: TokenValue::EOF, lnum: -1, line: "".to_string() },
-              index + 1);
+      return Ok((Token { value: TokenValue::EOF, lnum: -1, col: 0,
+                         line: "".to_string() }, index + 1));
     }
     index += 1;
     c = chars[index];
@@ -59,8 +67,8 @@ fn next_token(chars: &Vec<char>, start: usize, key: &LineLookup) ->
       }
       let s = chars[from + 1..index].iter().cloned().collect();
       if c != '"' {
-        parser::parse_error(format!("unterminated string in source: {}", s),
-                            -1, &"EOF".to_string());
+        return Err(parser::parse_error(format!("unterminated string in source: {}", s),
+                                       -1, "EOF"));
       }
       (TokenValue::String(s), index + 1)
     },
@@ -75,13 +83,21 @@ fn next_token(chars: &Vec<char>, start: usize, key: &LineLookup) ->
         (TokenValue::True, index)
       } else if s == "false" {
         (TokenValue::False, index)
+      } else if s.len() > 1 && s.starts_with('\'') {
+        (TokenValue::Symbol(Symbol::intern(&s[1..])), index)
       } else {
         match s.parse::<i64>() {
           Ok(n) => (TokenValue::Integer(n), index),
           _ => {
-            match s.parse::<f64>() {
-              Ok(n) => (TokenValue::Float(n), index),
-              _=> (TokenValue::ID(s), index),
+            // Integers too big for an int are big integers, not floats
+            match BigInt::parse(&s) {
+              Some(n) => (TokenValue::BigInt(n), index),
+              None => {
+                match s.parse::<f64>() {
+                  Ok(n) => (TokenValue::Float(n), index),
+                  _=> (TokenValue::ID(s), index),
+                }
+              },
             }
           },
         }
@@ -89,12 +105,13 @@ fn next_token(chars: &Vec<char>, start: usize, key: &LineLookup) ->
     },
   };
   let token = Token { value: value, lnum: get_lnum(pos, key) as isize,
-                      line: get_line(pos, key) };
-  (token, pos)
+                      col: get_col(from, key), line: get_line(pos, key) };
+  Ok((token, pos))
 }
 
 pub fn build_line_key(chars: &Vec<char>) -> LineLookup {
-  let mut key = LineLookup { lnums: Vec::new(), lines: Vec::new() };
+  let mut key = LineLookup { lnums: Vec::new(), lines: Vec::new(),
+                             starts: vec![0] };
 
   let mut line_num = 0;
   let mut start = 0;
@@ -105,6 +122,7 @@ pub fn build_line_key(chars: &Vec<char>) -> LineLookup {
       let line = chars[start..current].iter().cloned().collect();
       key.lines.push(line);
       start = current + 1;
+      key.starts.push(start);
       line_num += 1;
     }
     current += 1;
@@ -112,7 +130,7 @@ pub fn build_line_key(chars: &Vec<char>) -> LineLookup {
   key
 }
 
-pub fn tokenize(s: &str) -> Vec<Token> {
+pub fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
   let chars = s.chars().collect();
   let key = build_line_key(&chars);
 
@@ -120,9 +138,9 @@ pub fn tokenize(s: &str) -> Vec<Token> {
 
   let mut index = 0;
   while index < chars.len() {
-    let (token, change) = next_token(&chars, index, &key);
+    let (token, change) = next_token(&chars, index, &key)?;
     index = change;
     tokens.push(token);
   }
-  tokens
+  Ok(tokens)
 }
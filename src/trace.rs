@@ -0,0 +1,168 @@
+// Execution trace: a line for every call once its arguments are evaluated,
+// and another when it finishes with its result (or exception), indented by
+// how many calls deep we are.  Parameter lookups (_, __, ...) aren't calls
+// for this purpose and don't show up
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+
+use encoding::Evaluation;
+use encoding::ListEval;
+
+pub struct Options {
+  // Only show calls to these (everything, if empty)
+  pub names: Vec<String>,
+  // Don't show anything deeper than this
+  pub max_depth: Option<usize>,
+  pub output: Box<dyn Write>
+}
+
+struct Tracer {
+  options: Options,
+  depth: usize
+}
+
+thread_local! {
+  static ACTIVE: Cell<bool> = const { Cell::new(false) };
+
+  static TRACER: RefCell<Option<Tracer>> = const { RefCell::new(None) };
+}
+
+pub fn start(options: Options) {
+  TRACER.with(|t| *t.borrow_mut() = Some(Tracer { options, depth: 0 }));
+  ACTIVE.with(|a| a.set(true));
+}
+
+// Stop tracing and make sure everything's been written out
+pub fn stop() {
+  ACTIVE.with(|a| a.set(false));
+  TRACER.with(|t| {
+    if let Some(mut tracer) = t.borrow_mut().take() {
+      tracer.options.output.flush().unwrap_or(());
+    }
+  });
+}
+
+pub fn active() -> bool {
+  ACTIVE.with(|a| a.get())
+}
+
+impl Tracer {
+  fn shown(&self, id: &str) -> bool {
+    let deep = match self.options.max_depth {
+      Some(max) => self.depth >= max,
+      None => false,
+    };
+    !deep && (self.options.names.is_empty() || self.options.names.iter().any(|n| n == id))
+  }
+
+  fn write(&mut self, line: String) {
+    let indent = "  ".repeat(self.depth);
+    if writeln!(self.options.output, "{}{}", indent, line).is_err() {
+      // Nowhere to write to any more; no point carrying on
+      ACTIVE.with(|a| a.set(false));
+    }
+  }
+}
+
+pub fn enter(id: &str, args: &ListEval) {
+  TRACER.with(|t| {
+    if let Some(ref mut tracer) = *t.borrow_mut() {
+      if tracer.shown(id) {
+        tracer.write(format!("-> {}{}", id, Evaluation::List(args.clone())));
+      }
+      tracer.depth += 1;
+    }
+  });
+}
+
+pub fn leave(id: &str, result: &Evaluation) {
+  TRACER.with(|t| {
+    if let Some(ref mut tracer) = *t.borrow_mut() {
+      tracer.depth -= 1;
+      if tracer.shown(id) {
+        let line = match *result {
+          Evaluation::Exception(ref ex) => {
+            format!("<- {} raised {} : {}", id, ex.flavor, ex.payload)
+          },
+          ref value => format!("<- {} = {}", id, value),
+        };
+        tracer.write(line);
+      }
+    }
+  });
+}
+
+// Default destination, so the trace doesn't get mixed up with the output
+pub fn stderr() -> Box<dyn Write> {
+  Box::new(io::stderr())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::rc::Rc;
+
+  use evaluator;
+  use parser;
+  use tokenizer;
+
+  // Somewhere to write the trace that we can still read afterwards
+  #[derive(Clone)]
+  struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Buffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  fn trace(source: &str, names: &[&str], max_depth: Option<usize>) -> String {
+    let buffer = Buffer(Rc::new(RefCell::new(Vec::new())));
+    start(Options { names: names.iter().map(|n| n.to_string()).collect(), max_depth,
+                    output: Box::new(buffer.clone()) });
+    let block = parser::parse(&tokenizer::tokenize(source).ok().unwrap()).ok().unwrap();
+    evaluator::evaluate(&block, &ListEval { items: Vec::new() });
+    stop();
+    let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    text
+  }
+
+  const SOURCE: &str = "inc:+[car[_] 1];;\ninc[1];\n";
+
+  #[test]
+  fn everything() {
+    assert_eq!(trace(SOURCE, &[], None), "\
+-> inc[1]
+  -> car[[1]]
+  <- car = 1
+  -> +[1 1]
+  <- + = 2
+<- inc = 2
+");
+  }
+
+  #[test]
+  fn depth() {
+    assert_eq!(trace(SOURCE, &[], Some(1)), "-> inc[1]\n<- inc = 2\n");
+    assert_eq!(trace(SOURCE, &[], Some(0)), "");
+  }
+
+  #[test]
+  fn names() {
+    // Indented by the real depth, even when what's around it isn't shown
+    assert_eq!(trace(SOURCE, &["+"], None), "  -> +[1 1]\n  <- + = 2\n");
+  }
+
+  #[test]
+  fn exceptions() {
+    assert_eq!(trace("car[1];\n", &[], None),
+               "-> car[1]\n<- car raised type error : \"car : list argument expected\"\n");
+  }
+}